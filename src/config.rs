@@ -0,0 +1,143 @@
+use crate::manager::{AnalysisTask, EvictionPolicy, Mode, ModeCgroup, Quota, QuotaSubject, Reservation};
+use crate::tuning::TuningProfile;
+
+/// Manager configuration file, in TOML format. Command line arguments take
+/// precedence over the values in the configuration file.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub tuning: TuningConfig,
+    /// Process placement rules, in the same format accepted by `rules/add`.
+    /// These are reloaded on SIGHUP.
+    pub rules: Vec<String>,
+    /// Bandwidth quotas of users and groups. These are reloaded on SIGHUP.
+    pub quotas: Vec<QuotaConfig>,
+    /// Authorization policy file for process operations. This is reloaded
+    /// on SIGHUP.
+    pub policy: Option<std::path::PathBuf>,
+    /// Managed cgroups created on startup, also used by `simulate`.
+    pub cgroups: Vec<CgroupConfig>,
+    /// Operating modes, switched to through `cgroup/transaction`. These are
+    /// reloaded on SIGHUP.
+    pub modes: Vec<ModeConfig>,
+}
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TuningConfig {
+    /// Tuning profile, `full` if not given.
+    pub profile: Option<TuningProfile>,
+    /// CPUs running the managed reservations, in the kernel list format. If
+    /// not given, the CPUs of the managed cgroups are tuned.
+    pub cpus: Option<String>,
+    /// Overrides the profile's hyperthreading step.
+    pub hyperthreading: Option<bool>,
+    /// Overrides the profile's frequency step.
+    pub frequency: Option<bool>,
+    /// Overrides the profile's idle states step.
+    pub idle_states: Option<bool>,
+}
+
+/// Quota of either a user or a group.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuotaConfig {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// Maximum total bandwidth, e.g. 0.3 for 30% of a CPU.
+    pub max_bandwidth: Option<f64>,
+    /// Maximum number of cgroups.
+    pub max_cgroups: Option<usize>,
+}
+
+/// Managed cgroup, with the tasks running inside it.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CgroupConfig {
+    pub name: String,
+    pub runtime_us: u64,
+    pub period_us: u64,
+    /// Task set, in the same format accepted by `cgroup/taskset`.
+    #[serde(default)]
+    pub tasks: Vec<String>,
+}
+
+/// Named set of managed cgroups, which replaces the cgroups of the other modes
+/// when switched to.
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModeConfig {
+    pub name: String,
+    /// Eviction policy of the deleted cgroups which still have processes,
+    /// one of `kill`, `demote` and `original`.
+    pub eviction: Option<EvictionPolicy>,
+    #[serde(default)]
+    pub cgroups: Vec<CgroupConfig>,
+}
+
+impl Config {
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+
+        let data = std::fs::read_to_string(path)
+            .map_err(|err| anyhow::format_err!("Cannot read configuration file {}: {err}", path.display()))?;
+
+        toml::from_str(&data)
+            .map_err(|err| anyhow::format_err!("Invalid configuration file {}: {err}", path.display()))
+    }
+}
+
+impl QuotaConfig {
+    pub fn quota(&self) -> anyhow::Result<Quota> {
+        let subject = match (self.uid, self.gid) {
+            (Some(uid), None) => QuotaSubject::User(uid),
+            (None, Some(gid)) => QuotaSubject::Group(gid),
+            _ => anyhow::bail!("Invalid quota: exactly one of uid and gid must be given"),
+        };
+
+        Ok(Quota {
+            subject,
+            max_bandwidth: self.max_bandwidth,
+            max_cgroups: self.max_cgroups,
+        })
+    }
+}
+
+impl CgroupConfig {
+    pub fn reservation(&self) -> Reservation {
+        Reservation {
+            runtime_us: self.runtime_us,
+            period_us: self.period_us,
+        }
+    }
+
+    pub fn tasks(&self) -> anyhow::Result<Vec<AnalysisTask>> {
+        self.tasks.iter()
+            .map(|task| task.parse::<AnalysisTask>()
+                .map_err(|err| anyhow::format_err!("Invalid task set of cgroup \"{}\": {err}", self.name)))
+            .collect()
+    }
+}
+
+impl ModeConfig {
+    pub fn mode(&self) -> anyhow::Result<Mode> {
+        if self.eviction == Some(EvictionPolicy::Leave) {
+            anyhow::bail!("Invalid mode \"{}\": cgroups cannot be left on deletion", self.name);
+        }
+
+        let cgroups =
+            self.cgroups.iter()
+            .map(|cgroup| Ok(ModeCgroup {
+                name: cgroup.name.clone(),
+                reservation: cgroup.reservation(),
+                tasks: cgroup.tasks()?,
+            }))
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Mode {
+            name: self.name.clone(),
+            cgroups,
+            eviction: self.eviction,
+        })
+    }
+}
@@ -0,0 +1,326 @@
+//! Readiness checks for running the manager on the current machine.
+
+use std::path::{Path, PathBuf};
+
+use hcbs_utils::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+    /// How to fix a failed or warning check.
+    pub hint: Option<&'static str>,
+}
+
+impl CheckResult {
+    fn pass(name: &'static str, message: String) -> Self {
+        Self { name, status: CheckStatus::Pass, message, hint: None }
+    }
+
+    fn warn(name: &'static str, message: String, hint: &'static str) -> Self {
+        Self { name, status: CheckStatus::Warn, message, hint: Some(hint) }
+    }
+
+    fn fail(name: &'static str, message: String, hint: &'static str) -> Self {
+        Self { name, status: CheckStatus::Fail, message, hint: Some(hint) }
+    }
+}
+
+impl std::fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "PASS"),
+            CheckStatus::Warn => write!(f, "WARN"),
+            CheckStatus::Fail => write!(f, "FAIL"),
+        }
+    }
+}
+
+/// Runs all the checks. Checks never stop on errors, which are reported as
+/// failed or warning checks instead.
+pub fn run_checks() -> Vec<CheckResult> {
+    let kernel_config = KernelConfig::load();
+
+    vec![
+        check_privileges(),
+        check_cgroup_v2(),
+        check_cpu_controller(),
+        check_hcbs(),
+        check_rt_group_sched(&kernel_config),
+        check_preempt_rt(&kernel_config),
+        check_fuse(),
+        check_smt(),
+        check_cpufreq(),
+        check_cpuidle(),
+        check_isolated_cpus(),
+        check_rt_tasks(),
+    ]
+}
+
+fn cgroup_root() -> PathBuf {
+    PathBuf::from(cgroup_abs_path(ROOT_CGROUP).to_string())
+}
+
+fn check_privileges() -> CheckResult {
+    const NAME: &str = "privileges";
+
+    if unsafe { libc::geteuid() } == 0 {
+        CheckResult::pass(NAME, "running as root".to_owned())
+    } else {
+        CheckResult::fail(NAME, "not running as root".to_owned(),
+            "run the manager with sudo")
+    }
+}
+
+fn check_cgroup_v2() -> CheckResult {
+    const NAME: &str = "cgroup v2";
+
+    let mounts = std::fs::read_to_string("/proc/mounts").unwrap_or_default();
+    let cgroup2_mount =
+        mounts.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .find(|fields| fields.get(2) == Some(&"cgroup2"))
+        .and_then(|fields| fields.get(1).map(|path| path.to_string()));
+
+    match cgroup2_mount {
+        Some(path) => CheckResult::pass(NAME, format!("cgroup2 mounted at {path}")),
+        None => CheckResult::warn(NAME, "no cgroup2 filesystem mounted".to_owned(),
+            "the manager mounts it on startup; boot with systemd.unified_cgroup_hierarchy=1 if mounting fails"),
+    }
+}
+
+fn check_cpu_controller() -> CheckResult {
+    const NAME: &str = "cpu controller";
+
+    let path = cgroup_root().join("cgroup.controllers");
+    match std::fs::read_to_string(&path) {
+        Ok(controllers) if controllers.split_whitespace().any(|c| c == "cpu") =>
+            CheckResult::pass(NAME, "cpu controller available".to_owned()),
+        Ok(_) => CheckResult::fail(NAME, "cpu controller not available".to_owned(),
+            "release the cpu controller from cgroup v1 hierarchies, or boot with cgroup_no_v1=all"),
+        Err(err) => CheckResult::warn(NAME, format!("cannot read {}: {err}", path.display()),
+            "the manager mounts the cgroup filesystem on startup; run the check again while it is running"),
+    }
+}
+
+fn check_hcbs() -> CheckResult {
+    const NAME: &str = "HCBS";
+
+    let result = (|| -> anyhow::Result<_> {
+        Ok((get_cgroup_period_us(ROOT_CGROUP)?, get_cgroup_runtime_us(ROOT_CGROUP)?))
+    })();
+
+    match result {
+        Ok((period_us, runtime_us)) =>
+            CheckResult::pass(NAME, format!("root runtime {runtime_us}us, period {period_us}us")),
+        Err(err) => CheckResult::fail(NAME, format!("cannot read the root cgroup runtime files: {err}"),
+            "boot a kernel with the HCBS patchset, see https://github.com/Yurand2000/HCBS-patch"),
+    }
+}
+
+fn check_rt_group_sched(config: &KernelConfig) -> CheckResult {
+    const NAME: &str = "RT_GROUP_SCHED";
+
+    match config.enabled("CONFIG_RT_GROUP_SCHED") {
+        Some(true) => CheckResult::pass(NAME, "enabled".to_owned()),
+        Some(false) => CheckResult::fail(NAME, "disabled in the kernel configuration".to_owned(),
+            "rebuild the kernel with CONFIG_RT_GROUP_SCHED=y"),
+        None => CheckResult::warn(NAME, "kernel configuration not found".to_owned(),
+            "install the kernel configuration in /boot or enable CONFIG_IKCONFIG_PROC"),
+    }
+}
+
+fn check_preempt_rt(config: &KernelConfig) -> CheckResult {
+    const NAME: &str = "PREEMPT_RT";
+
+    let realtime = std::fs::read_to_string("/sys/kernel/realtime")
+        .is_ok_and(|data| data.trim() == "1");
+
+    if realtime || config.enabled("CONFIG_PREEMPT_RT") == Some(true) {
+        CheckResult::pass(NAME, "enabled".to_owned())
+    } else {
+        CheckResult::warn(NAME, "not a PREEMPT_RT kernel".to_owned(),
+            "use a PREEMPT_RT kernel for lower scheduling latencies")
+    }
+}
+
+fn check_fuse() -> CheckResult {
+    const NAME: &str = "FUSE";
+
+    if Path::new("/dev/fuse").exists() {
+        CheckResult::pass(NAME, "/dev/fuse available".to_owned())
+    } else {
+        CheckResult::fail(NAME, "/dev/fuse not found".to_owned(),
+            "load the fuse kernel module with \"modprobe fuse\"")
+    }
+}
+
+fn check_smt() -> CheckResult {
+    const NAME: &str = "SMT control";
+
+    match std::fs::read_to_string("/sys/devices/system/cpu/smt/control") {
+        Ok(state) => CheckResult::pass(NAME, format!("hyperthreading {}", state.trim())),
+        Err(_) => CheckResult::warn(NAME, "SMT control interface not found".to_owned(),
+            "hyperthreading cannot be disabled, use \"--tuning-profile frequency\" or disable it from the firmware"),
+    }
+}
+
+fn check_cpufreq() -> CheckResult {
+    const NAME: &str = "cpufreq";
+
+    match crate::tuning::tuning_backend(crate::tuning::TuningBackendKind::Auto) {
+        Ok(backend) if backend.name() != "none" =>
+            CheckResult::pass(NAME, format!("using the \"{}\" tuning backend", backend.name())),
+        Ok(_) => CheckResult::warn(NAME, "no frequency scaling interface found".to_owned(),
+            "CPU frequencies cannot be fixed, make sure they are fixed from the firmware"),
+        Err(err) => CheckResult::fail(NAME, format!("cannot detect the tuning backend: {err}"),
+            "pick a backend with \"--tuning-backend\", or disable tuning with \"--no-system-tuning\""),
+    }
+}
+
+fn check_cpuidle() -> CheckResult {
+    const NAME: &str = "cpuidle";
+
+    if Path::new("/sys/devices/system/cpu/cpu0/cpuidle").exists() {
+        CheckResult::pass(NAME, "cpuidle interface available".to_owned())
+    } else {
+        CheckResult::warn(NAME, "cpuidle interface not found".to_owned(),
+            "idle states cannot be disabled, boot with idle=poll or disable them from the firmware")
+    }
+}
+
+fn check_isolated_cpus() -> CheckResult {
+    const NAME: &str = "isolated CPUs";
+
+    let read_list = |path: &str| {
+        std::fs::read_to_string(path)
+            .map(|data| data.trim().to_owned())
+            .unwrap_or_default()
+    };
+
+    let isolated = read_list("/sys/devices/system/cpu/isolated");
+    let nohz_full = read_list("/sys/devices/system/cpu/nohz_full");
+
+    // nohz_full reads "(null)" on some kernels when not set.
+    let nohz_full = if nohz_full == "(null)" { String::new() } else { nohz_full };
+
+    if isolated.is_empty() && nohz_full.is_empty() {
+        CheckResult::warn(NAME, "no isolated or nohz_full CPUs".to_owned(),
+            "isolate the CPUs running the reservations with isolcpus= and nohz_full= on the kernel command line")
+    } else {
+        CheckResult::pass(NAME, format!("isolcpus \"{isolated}\", nohz_full \"{nohz_full}\""))
+    }
+}
+
+fn check_rt_tasks() -> CheckResult {
+    const NAME: &str = "real-time tasks";
+
+    let tasks = rt_user_tasks();
+
+    if tasks.is_empty() {
+        CheckResult::pass(NAME, "no user real-time tasks running".to_owned())
+    } else {
+        CheckResult::warn(NAME, format!("real-time tasks running outside the manager: {}", tasks.join(", ")),
+            "these tasks compete with the reservations, stop them or switch them to SCHED_OTHER")
+    }
+}
+
+/// User space tasks running with a real-time scheduling policy, except this
+/// process.
+fn rt_user_tasks() -> Vec<String> {
+    const PF_KTHREAD: u64 = 0x00200000;
+
+    let Ok(entries) = std::fs::read_dir("/proc")
+        else { return Vec::with_capacity(0); };
+
+    let this_pid = std::process::id();
+
+    entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok())
+        .filter(|&pid| pid != this_pid)
+        .filter_map(|pid| {
+            let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+
+            // The command name may contain spaces, fields are parsed after it.
+            let (head, tail) = stat.rsplit_once(')')?;
+            let comm = head.split_once('(')?.1;
+            let fields: Vec<_> = tail.split_whitespace().collect();
+
+            let flags: u64 = fields.get(6)?.parse().ok()?;
+            let rt_priority: u32 = fields.get(37)?.parse().ok()?;
+            let policy = match *fields.get(38)? {
+                "1" => "SCHED_FIFO",
+                "2" => "SCHED_RR",
+                "6" => "SCHED_DEADLINE",
+                _ => return None,
+            };
+
+            if flags & PF_KTHREAD != 0 {
+                return None;
+            }
+
+            Some(format!("{pid} ({comm}, {policy} {rt_priority})"))
+        })
+        .collect()
+}
+
+/// Kernel build configuration, read from /proc/config.gz if uncompressed
+/// copies are not available in /boot.
+struct KernelConfig {
+    data: Option<String>,
+}
+
+impl KernelConfig {
+    fn load() -> Self {
+        let release = std::fs::read_to_string("/proc/sys/kernel/osrelease")
+            .map(|release| release.trim().to_owned())
+            .unwrap_or_default();
+
+        let data = std::fs::read_to_string(format!("/boot/config-{release}")).ok()
+            .or_else(|| {
+                std::process::Command::new("zcat")
+                    .arg("/proc/config.gz")
+                    .output().ok()
+                    .filter(|output| output.status.success())
+                    .and_then(|output| String::from_utf8(output.stdout).ok())
+            });
+
+        Self { data }
+    }
+
+    /// Whether the given option is built in, `None` if the configuration is
+    /// not available.
+    fn enabled(&self, option: &str) -> Option<bool> {
+        let data = self.data.as_ref()?;
+        let enabled = format!("{option}=y");
+
+        Some(data.lines().any(|line| line.trim() == enabled))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rt_group_sched_follows_the_kernel_config() {
+        let config = |data: Option<&str>| KernelConfig { data: data.map(str::to_owned) };
+
+        let enabled = config(Some("CONFIG_FAIR_GROUP_SCHED=y\nCONFIG_RT_GROUP_SCHED=y\n"));
+        assert_eq!(check_rt_group_sched(&enabled).status, CheckStatus::Pass);
+
+        let disabled = config(Some("# CONFIG_RT_GROUP_SCHED is not set\n"));
+        assert_eq!(disabled.enabled("CONFIG_RT_GROUP_SCHED"), Some(false));
+        assert_eq!(check_rt_group_sched(&disabled).status, CheckStatus::Fail);
+
+        assert_eq!(check_rt_group_sched(&config(None)).status, CheckStatus::Warn);
+    }
+}
@@ -2,42 +2,58 @@ use fuser::*;
 use std::collections::HashMap;
 use crate::filesystem::utils::*;
 use crate::ProcessStats;
+use crate::manager::Requester;
 
+mod admission_file;
+mod capabilities_file;
 mod proc_dir;
 mod cgroup_dir;
+mod rules_dir;
 mod utils;
 
+use admission_file::*;
+use capabilities_file::*;
 use proc_dir::*;
 use cgroup_dir::*;
+use rules_dir::*;
+
+pub(crate) use utils::parser;
+pub(crate) use utils::cgroup_to_dir_name;
+pub(crate) use cgroup_dir::RESERVED_NAMES as RESERVED_CGROUP_NAMES;
 
 impl Filesystem for super::Controller {
     fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &std::ffi::OsStr, reply: ReplyEntry) {
-        RootFS::new(self)
+        RootFS::new(&mut self.state(), _req)
             .lookup(_req, parent, name, reply);
     }
 
     fn getattr(&mut self, _req: &Request<'_>, ino: u64, fh: Option<u64>, reply: ReplyAttr) {
-        RootFS::new(self)
+        RootFS::new(&mut self.state(), _req)
             .getattr(_req, ino, fh, reply);
     }
 
     fn setattr(&mut self, _req: &Request<'_>, ino: u64, mode: Option<u32>, uid: Option<u32>, gid: Option<u32>, size: Option<u64>, _atime: Option<TimeOrNow>, _mtime: Option<TimeOrNow>, _ctime: Option<std::time::SystemTime>, fh: Option<u64>, _crtime: Option<std::time::SystemTime>, _chgtime: Option<std::time::SystemTime>, _bkuptime: Option<std::time::SystemTime>, flags: Option<u32>, reply: ReplyAttr) {
-        RootFS::new(self)
+        RootFS::new(&mut self.state(), _req)
             .setattr(_req, ino, mode, uid, gid, size, _atime, _mtime, _ctime, fh, _crtime, _chgtime, _bkuptime, flags, reply);
     }
 
     fn read(&mut self, _req: &Request<'_>, ino: u64, fh: u64, offset: i64, size: u32, flags: i32, lock_owner: Option<u64>, reply: ReplyData) {
-        RootFS::new(self)
+        RootFS::new(&mut self.state(), _req)
             .read(_req, ino, fh, offset, size, flags, lock_owner, reply);
     }
 
     fn write(&mut self, _req: &Request<'_>, ino: u64, fh: u64, offset: i64, data: &[u8], write_flags: u32, flags: i32, lock_owner: Option<u64>, reply: ReplyWrite) {
-        RootFS::new(self)
+        let mut state = self.state();
+
+        RootFS::new(&mut state, _req)
             .write(_req, ino, fh, offset, data, write_flags, flags, lock_owner, reply);
+
+        // Writes can create and destroy cgroups.
+        state.retune();
     }
 
     fn readdir(&mut self, _req: &Request<'_>, ino: u64, fh: u64, offset: i64, reply: ReplyDirectory) {
-        RootFS::new(self)
+        RootFS::new(&mut self.state(), _req)
             .readdir(_req, ino, fh, offset, reply);
     }
 }
@@ -46,15 +62,17 @@ impl Filesystem for super::Controller {
 pub struct RootFS<'a> {
     active_procs: &'a HashMap<sysinfo::Pid, ProcessStats>,
     manager: &'a mut crate::manager::HCBSManager,
+    requester: Requester,
 }
 
 impl<'a> RootFS<'a> {
-    pub fn new(controller: &'a mut super::Controller) -> DirFS<Self> {
+    pub fn new(controller: &'a mut super::ControllerState, req: &Request<'_>) -> DirFS<Self> {
         controller.update();
 
         DirFS::new( Self {
             active_procs: &controller.process_info.active_procs,
-            manager: &mut controller.manager
+            manager: &mut controller.manager,
+            requester: Requester { uid: req.uid(), gid: req.gid(), pid: req.pid() },
         } )
     }
 }
@@ -66,19 +84,25 @@ impl DirFSInterface for RootFS<'_> {
         match name.to_str().unwrap() {
             ProcDirFS::NAME => Some(Box::new(ProcDirFS::new(self))),
             CgroupDirFS::NAME => Some(Box::new(CgroupDirFS::new(self))),
+            RulesDirFS::NAME => Some(Box::new(RulesDirFS::new(self))),
+            CapabilitiesFileFS::NAME => Some(Box::new(CapabilitiesFileFS::new(self))),
+            AdmissionFileFS::NAME => Some(Box::new(AdmissionFileFS::new(self))),
             _ => None,
         }
     }
 
     fn fs_from_inode<'a>(&'a mut self, inode: u64) -> Option<Box<dyn VirtualFS + 'a>> {
         match inode & INODE_DIR_TYPE_MASK {
-            ROOT_DIR_INODE => match inode & INODE_DIR_FILE_MASK {
+            ROOT_INODE_DIR_TYPE => match inode & INODE_DIR_FILE_MASK {
                 0 => panic!("inode zero"),
                 1 => panic!("recursion"),
+                _ if inode == CapabilitiesFileFS::INODE => Some(Box::new(CapabilitiesFileFS::new(self))),
+                _ if inode == AdmissionFileFS::INODE => Some(Box::new(AdmissionFileFS::new(self))),
                 _ => None,
             },
             PROC_DIR_INODE => Some(Box::new(ProcDirFS::new(self))),
             CGROUP_DIR_INODE => Some(Box::new(CgroupDirFS::new(self))),
+            RULES_DIR_INODE => Some(Box::new(RulesDirFS::new(self))),
             _ => None,
         }
     }
@@ -87,6 +111,9 @@ impl DirFSInterface for RootFS<'_> {
         [
             PROC_DIR_INODE,
             CGROUP_DIR_INODE,
+            RULES_DIR_INODE,
+            CapabilitiesFileFS::INODE,
+            AdmissionFileFS::INODE,
         ].into_iter()
     }
 }
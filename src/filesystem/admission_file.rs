@@ -0,0 +1,100 @@
+use fuser::*;
+use crate::filesystem::utils::*;
+use crate::manager::Reservation;
+
+#[derive(Debug)]
+pub struct AdmissionFileFS<'a> {
+    manager: &'a mut crate::manager::HCBSManager,
+    requester: crate::manager::Requester,
+    verdicts: String,
+}
+
+impl<'a> AdmissionFileFS<'a> {
+    pub const NAME: &'static str = "admission";
+    pub const INODE: u64 = ROOT_INODE_DIR_TYPE + 3;
+
+    pub fn new(root_fs: &'a mut super::RootFS<'_>) -> FileFS<Self> {
+        let mut verdicts = format!("policy {}\n", root_fs.manager.admission_policy().name());
+
+        let mut entries: Vec<_> = root_fs.manager.admission_verdicts().collect();
+        entries.sort_by_key(|(name, _, _)| *name);
+        for (name, request, verdict) in entries {
+            verdicts += &format!("{name} {} {} {verdict}\n", request.runtime_us, request.period_us);
+        }
+
+        if let Some((name, request, verdict)) = root_fs.manager.admission_evaluation(&root_fs.requester) {
+            verdicts += &format!("evaluation {name} {} {} {verdict}\n", request.runtime_us, request.period_us);
+        }
+
+        FileFS::new( Self {
+            verdicts,
+            manager: root_fs.manager,
+            requester: root_fs.requester,
+        } )
+    }
+
+    fn parse_request(data: &str) -> Option<(&str, Reservation)> {
+        use nom::Parser as _;
+        use nom::character::complete::*;
+        use nom::combinator::*;
+        use nom::sequence::*;
+
+        terminated(
+            separated_pair(
+                crate::filesystem::utils::parser::parse_cgroup_name,
+                space1,
+                crate::filesystem::utils::parser::parse_cgroup_alloc_request,
+            ),
+            (multispace0, eof),
+        ).parse(data).map(|(_, res)| res).ok()
+    }
+}
+
+impl FileFSInterface for AdmissionFileFS<'_> {
+    fn read_size(&self) -> anyhow::Result<usize> {
+        Ok(self.verdicts.len())
+    }
+
+    fn read_data(&self) -> anyhow::Result<&str> {
+        Ok(self.verdicts.as_str())
+    }
+
+    fn write_data(&mut self, data: &str) -> anyhow::Result<()> {
+        let Some((name, request)) = Self::parse_request(data)
+            else { anyhow::bail!("Invalid request"); };
+
+        self.manager.evaluate_admission(name, request, &self.requester)?;
+
+        Ok(())
+    }
+}
+
+impl VirtualFile for AdmissionFileFS<'_> {
+    fn inode(&self) -> u64 {
+        Self::INODE
+    }
+
+    fn attr(&self) -> FileAttr {
+        FileAttr {
+            ino: Self::INODE,
+            size: 0,
+            blocks: 0,
+            atime: UNKNOWN_TIME,
+            mtime: UNKNOWN_TIME,
+            ctime: UNKNOWN_TIME,
+            crtime: UNKNOWN_TIME,
+            kind: FileType::RegularFile,
+            perm: 0o666,
+            nlink: 1,
+            uid: ROOT_UID,
+            gid: ROOT_GID,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
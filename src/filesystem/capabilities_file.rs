@@ -0,0 +1,104 @@
+use fuser::*;
+use crate::filesystem::utils::*;
+use crate::manager::Capabilities;
+
+#[derive(Debug)]
+pub struct CapabilitiesFileFS {
+    capabilities: String,
+}
+
+impl CapabilitiesFileFS {
+    pub const NAME: &'static str = "capabilities";
+    pub const INODE: u64 = ROOT_INODE_DIR_TYPE + 2;
+
+    /// Accepted request formats, by file.
+    const GRAMMARS: &'static [(&'static str, &'static str)] = &[
+        ("admission", "<cgroup name> <runtime us> <period us>"),
+        ("cgroup/create", "<cgroup name> <runtime us> <period us> [prio=<min>-<max>] [max=<runtime us> [weight=<n>]] [cpus=<cpu list>] [--best-effort]"),
+        ("cgroup/update", "<cgroup name> <runtime us> <period us> [prio=<min>-<max>|none] [max=<runtime us> [weight=<n>]|max=none] [cpus=<cpu list>]"),
+        ("cgroup/delete", "<cgroup name> [kill|demote|original]"),
+        ("cgroup/taskset", "<cgroup name> [<wcet us>,<period us>,<deadline us>,<priority>...]"),
+        ("cgroup/size", "<cgroup name> [--create] <wcet us>,<period us>,<deadline us>,<priority> [...]"),
+        ("cgroup/transaction", "mode <mode name> | <step> [<newline> <step>...] where <step> is create <cgroup/create request> without --best-effort, update <cgroup/update request> or delete <cgroup name> [kill|demote|original]"),
+        ("proc/<PID>/cgroup", "<cgroup name> [--threads|--tree]"),
+        ("proc/<PID>/sched_policy", "<sched policy> [--threads|--tree]"),
+        ("proc/<PID>/placement", "<cgroup name> <sched policy>"),
+        ("proc/<PID>/release", "<any>"),
+        ("proc/<PID>/timing", "<period us> [<deadline us> [<wcet us>]] | clear"),
+        ("rules/add", "<rule name> <match> [<match>...] -> <cgroup name> <sched policy>"),
+        ("rules/delete", "<rule name>"),
+    ];
+
+    pub fn new(root_fs: &mut super::RootFS<'_>) -> FileFS<Self> {
+        FileFS::new( Self { capabilities: Self::format(root_fs.manager.capabilities()) } )
+    }
+
+    fn format(capabilities: &Capabilities) -> String {
+        let yes_no = |value: bool| if value { "yes" } else { "no" };
+
+        let mut data = format!(
+            "version {}\n\
+            multi_runtime {}\n\
+            deadline_servers {}\n\
+            cpuset {}\n\
+            min_period_us {}\n\
+            max_period_us {}\n",
+            env!("CARGO_PKG_VERSION"),
+            yes_no(capabilities.multi_runtime),
+            yes_no(capabilities.deadline_servers),
+            yes_no(capabilities.cpuset),
+            capabilities.min_period_us,
+            capabilities.max_period_us,
+        );
+
+        for (file, grammar) in Self::GRAMMARS.iter() {
+            data += &format!("grammar {file} {grammar}\n");
+        }
+
+        data
+    }
+}
+
+impl FileFSInterface for CapabilitiesFileFS {
+    fn read_size(&self) -> anyhow::Result<usize> {
+        Ok(self.capabilities.len())
+    }
+
+    fn read_data(&self) -> anyhow::Result<&str> {
+        Ok(self.capabilities.as_str())
+    }
+
+    fn write_data(&mut self, _data: &str) -> anyhow::Result<()> {
+        anyhow::bail!("Cannot write to CapabilitiesFile")
+    }
+}
+
+impl VirtualFile for CapabilitiesFileFS {
+    fn inode(&self) -> u64 {
+        Self::INODE
+    }
+
+    fn attr(&self) -> FileAttr {
+        FileAttr {
+            ino: Self::INODE,
+            size: 0,
+            blocks: 0,
+            atime: UNKNOWN_TIME,
+            mtime: UNKNOWN_TIME,
+            ctime: UNKNOWN_TIME,
+            crtime: UNKNOWN_TIME,
+            kind: FileType::RegularFile,
+            perm: 0o444,
+            nlink: 1,
+            uid: ROOT_UID,
+            gid: ROOT_GID,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
@@ -4,14 +4,37 @@ use crate::filesystem::utils::*;
 mod create_cgroup_file;
 mod update_cgroup_file;
 mod delete_cgroup_file;
+mod managed_cgroup_dir;
+mod quota_file;
+mod size_file;
+mod taskset_file;
+mod transaction_file;
 
 use create_cgroup_file::*;
 use update_cgroup_file::*;
 use delete_cgroup_file::*;
+use managed_cgroup_dir::*;
+use quota_file::*;
+use size_file::*;
+use taskset_file::*;
+use transaction_file::*;
+
+/// Names of the files in the cgroup folder, which managed cgroups' folders
+/// cannot take.
+pub const RESERVED_NAMES: &[&str] = &[
+    CreateCgroupFileFS::NAME,
+    DeleteCgroupFileFS::NAME,
+    UpdateCgroupFileFS::NAME,
+    QuotaFileFS::NAME,
+    TasksetFileFS::NAME,
+    SizeFileFS::NAME,
+    TransactionFileFS::NAME,
+];
 
 #[derive(Debug)]
 pub struct CgroupDirFS<'a> {
     manager: &'a mut crate::manager::HCBSManager,
+    requester: crate::manager::Requester,
     root_fs_attr: FileAttr,
 }
 
@@ -23,6 +46,7 @@ impl<'a> CgroupDirFS<'a> {
 
         DirFS::new( Self {
             manager: root_fs.manager,
+            requester: root_fs.requester,
             root_fs_attr,
         } )
     }
@@ -38,7 +62,12 @@ impl DirFSInterface for CgroupDirFS<'_> {
             CreateCgroupFileFS::NAME => Some(Box::new(CreateCgroupFileFS::new(self))),
             DeleteCgroupFileFS::NAME => Some(Box::new(DeleteCgroupFileFS::new(self))),
             UpdateCgroupFileFS::NAME => Some(Box::new(UpdateCgroupFileFS::new(self))),
-            _ => None,
+            QuotaFileFS::NAME => Some(Box::new(QuotaFileFS::new(self))),
+            TasksetFileFS::NAME => Some(Box::new(TasksetFileFS::new(self))),
+            SizeFileFS::NAME => Some(Box::new(SizeFileFS::new(self))),
+            TransactionFileFS::NAME => Some(Box::new(TransactionFileFS::new(self))),
+            name => ManagedCgroupDirFS::new_from_name(self, name)
+                .map(|fs| -> Box<dyn VirtualFS + 'a> { Box::new(fs) }),
         }
     }
 
@@ -47,6 +76,12 @@ impl DirFSInterface for CgroupDirFS<'_> {
             CreateCgroupFileFS::INODE => Some(Box::new(CreateCgroupFileFS::new(self))),
             DeleteCgroupFileFS::INODE => Some(Box::new(DeleteCgroupFileFS::new(self))),
             UpdateCgroupFileFS::INODE => Some(Box::new(UpdateCgroupFileFS::new(self))),
+            QuotaFileFS::INODE => Some(Box::new(QuotaFileFS::new(self))),
+            TasksetFileFS::INODE => Some(Box::new(TasksetFileFS::new(self))),
+            SizeFileFS::INODE => Some(Box::new(SizeFileFS::new(self))),
+            TransactionFileFS::INODE => Some(Box::new(TransactionFileFS::new(self))),
+            _ if inode & INODE_DIR_ID_MASK != 0 => ManagedCgroupDirFS::new_from_inode(self, inode)
+                .map(|fs| -> Box<dyn VirtualFS + 'a> { Box::new(fs) }),
             _ => None,
         }
     }
@@ -56,7 +91,15 @@ impl DirFSInterface for CgroupDirFS<'_> {
             CreateCgroupFileFS::INODE,
             DeleteCgroupFileFS::INODE,
             UpdateCgroupFileFS::INODE,
+            QuotaFileFS::INODE,
+            TasksetFileFS::INODE,
+            SizeFileFS::INODE,
+            TransactionFileFS::INODE,
         ].into_iter()
+        .chain(
+            self.manager.managed_cgroups()
+                .map(|(name, _)| cgroup_to_dir_inode(name))
+        )
     }
 }
 
@@ -1,9 +1,11 @@
 use fuser::*;
-use crate::{filesystem::utils::*, manager::Reservation};
+use crate::{filesystem::utils::*, manager::{Elasticity, PriorityRange, Reservation}};
+use hcbs_utils::prelude::CpuID;
 
 #[derive(Debug)]
 pub struct CreateCgroupFileFS<'a> {
     cgroup_manager: &'a mut crate::manager::HCBSManager,
+    requester: crate::manager::Requester,
 }
 
 impl<'a> CreateCgroupFileFS<'a> {
@@ -11,21 +13,36 @@ impl<'a> CreateCgroupFileFS<'a> {
     pub const INODE: u64 = CGROUP_DIR_INODE + 1;
 
     pub fn new(cgroup_dir_fs: &'a mut super::CgroupDirFS<'_>) -> FileFS<Self> {
-        FileFS::new( Self { cgroup_manager: cgroup_dir_fs.manager } )
+        FileFS::new( Self {
+            cgroup_manager: cgroup_dir_fs.manager,
+            requester: cgroup_dir_fs.requester,
+        } )
     }
 
-    fn parse_request(data: &str) -> Option<(&str, Reservation)> {
+    /// With `--best-effort`, the cgroup is created with the largest runtime up
+    /// to the requested one which passes admission.
+    fn parse_request(data: &str) -> Option<(&str, Reservation, Option<PriorityRange>, Option<Elasticity>, Option<Vec<CpuID>>, bool)> {
         use nom::Parser as _;
+        use nom::bytes::complete::*;
         use nom::character::complete::*;
         use nom::combinator::*;
+        use nom::sequence::*;
 
-        map(
-            (
-                crate::filesystem::utils::parser::parse_cgroup_name,
-                space1,
-                crate::filesystem::utils::parser::parse_cgroup_alloc_request,
+        // Misordered or unknown options must not be silently dropped.
+        terminated(
+            map(
+                (
+                    crate::filesystem::utils::parser::parse_cgroup_name,
+                    space1,
+                    crate::filesystem::utils::parser::parse_cgroup_alloc_request,
+                    opt(preceded(space1, crate::filesystem::utils::parser::parse_priority_range)),
+                    opt(preceded(space1, crate::filesystem::utils::parser::parse_elasticity)),
+                    opt(preceded(space1, crate::filesystem::utils::parser::parse_cpus)),
+                    map(opt(preceded(space1, tag("--best-effort"))), |best_effort| best_effort.is_some()),
+                ),
+                |(name, _, request, priorities, elasticity, cpus, best_effort)| (name, request, priorities, elasticity, cpus, best_effort)
             ),
-            |(name, _, request)| (name, request)
+            (multispace0, eof),
         ).parse(data).map(|(_, res)| res).ok()
     }
 }
@@ -36,10 +53,15 @@ impl FileFSInterface for CreateCgroupFileFS<'_> {
     fn read_data(&self) -> anyhow::Result<&str> { anyhow::bail!("Cannot read from CreateCgroupFile") }
 
     fn write_data(&mut self, data: &str) -> anyhow::Result<()> {
-        let Some((name, request)) = Self::parse_request(data)
+        let Some((name, request, priorities, elasticity, cpus, best_effort)) = Self::parse_request(data)
             else { anyhow::bail!("Invalid request"); };
 
-        self.cgroup_manager.create_cgroup(name, request)
+        if best_effort {
+            self.cgroup_manager.create_cgroup_best_effort(name, request, priorities, elasticity, cpus, &self.requester)?;
+            return Ok(());
+        }
+
+        self.cgroup_manager.create_cgroup(name, request, priorities, elasticity, cpus, &self.requester)
     }
 }
 
@@ -71,4 +93,16 @@ impl VirtualFile for CreateCgroupFileFS<'_> {
     fn name(&self) -> &str {
         Self::NAME
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn options_must_be_in_order() {
+        assert!(CreateCgroupFileFS::parse_request("camera 1000 10000 prio=10-20 max=5000 --best-effort\n").is_some());
+        assert!(CreateCgroupFileFS::parse_request("camera 1000 10000 max=5000 prio=10-20").is_none());
+        assert!(CreateCgroupFileFS::parse_request("camera 1000 10000 prio=10-20 --best-efort").is_none());
+    }
+}
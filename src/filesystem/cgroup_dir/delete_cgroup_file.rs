@@ -1,9 +1,11 @@
 use fuser::*;
 use crate::filesystem::utils::*;
+use crate::manager::EvictionPolicy;
 
 #[derive(Debug)]
 pub struct DeleteCgroupFileFS<'a> {
     cgroup_manager: &'a mut crate::manager::HCBSManager,
+    requester: crate::manager::Requester,
 }
 
 impl<'a> DeleteCgroupFileFS<'a> {
@@ -11,12 +13,25 @@ impl<'a> DeleteCgroupFileFS<'a> {
     pub const INODE: u64 = CGROUP_DIR_INODE + 2;
 
     pub fn new(cgroup_dir_fs: &'a mut super::CgroupDirFS<'_>) -> FileFS<Self> {
-        FileFS::new( Self { cgroup_manager: cgroup_dir_fs.manager } )
+        FileFS::new( Self {
+            cgroup_manager: cgroup_dir_fs.manager,
+            requester: cgroup_dir_fs.requester,
+        } )
     }
 
-    fn parse_request(data: &str) -> Option<&str> {
-        crate::filesystem::utils::
-            parser::parse_cgroup_name(data).map(|(_, res)| res).ok()
+    fn parse_request(data: &str) -> Option<(&str, Option<EvictionPolicy>)> {
+        use nom::Parser as _;
+        use nom::character::complete::*;
+        use nom::combinator::*;
+        use nom::sequence::*;
+
+        terminated(
+            (
+                crate::filesystem::utils::parser::parse_cgroup_name,
+                opt(preceded(space1, crate::filesystem::utils::parser::parse_eviction_policy)),
+            ),
+            (multispace0, eof),
+        ).parse(data).map(|(_, res)| res).ok()
     }
 }
 
@@ -26,10 +41,10 @@ impl FileFSInterface for DeleteCgroupFileFS<'_> {
     fn read_data(&self) -> anyhow::Result<&str> { anyhow::bail!("Cannot read from DeleteCgroupFile") }
 
     fn write_data(&mut self, data: &str) -> anyhow::Result<()> {
-        let Some(name) = Self::parse_request(data)
+        let Some((name, eviction)) = Self::parse_request(data)
             else { anyhow::bail!("Invalid request"); };
 
-        self.cgroup_manager.destroy_cgroup(name)
+        self.cgroup_manager.destroy_cgroup(name, eviction, &self.requester)
     }
 }
 
@@ -0,0 +1,126 @@
+use fuser::*;
+use crate::filesystem::utils::*;
+use crate::manager::CgroupData;
+
+mod priorities_file;
+mod reservation_file;
+mod tasks_file;
+
+use priorities_file::*;
+use reservation_file::*;
+use tasks_file::*;
+
+/// Directory of a managed cgroup, owned by the user which created it.
+#[derive(Debug)]
+pub struct ManagedCgroupDirFS<'a> {
+    cgroup: &'a str,
+    name: String,
+    data: &'a CgroupData,
+    cgroup_dir_attr: FileAttr,
+}
+
+impl<'a> ManagedCgroupDirFS<'a> {
+    pub fn new_from_name(
+        cgroup_dir: &'a mut super::CgroupDirFS<'_>,
+        name: &str,
+    ) -> Option<DirFS<Self>> {
+        let cgroup_dir_attr = cgroup_dir.attr();
+
+        let (cgroup, data) =
+            cgroup_dir.manager.managed_cgroups()
+            .find(|(cgroup, _)| cgroup_to_dir_name(cgroup) == name)?;
+
+        Some(DirFS::new(Self {
+            cgroup,
+            name: name.to_owned(),
+            data,
+            cgroup_dir_attr,
+        }))
+    }
+
+    pub fn new_from_inode(
+        cgroup_dir: &'a mut super::CgroupDirFS<'_>,
+        inode: u64,
+    ) -> Option<DirFS<Self>> {
+        let cgroup_dir_attr = cgroup_dir.attr();
+        let dir_inode = inode & !INODE_DIR_FILE_MASK;
+
+        let (cgroup, data) =
+            cgroup_dir.manager.managed_cgroups()
+            .find(|(cgroup, _)| cgroup_to_dir_inode(cgroup) == dir_inode)?;
+
+        Some(DirFS::new(Self {
+            cgroup,
+            name: cgroup_to_dir_name(cgroup),
+            data,
+            cgroup_dir_attr,
+        }))
+    }
+}
+
+impl DirFSInterface for ManagedCgroupDirFS<'_> {
+    fn parent_attr(&self) -> Option<FileAttr> {
+        Some(self.cgroup_dir_attr)
+    }
+
+    fn fs_from_file_name<'a>(&'a mut self, name: &std::ffi::OsStr) -> Option<Box<dyn VirtualFS + 'a>> {
+        match name.to_str().unwrap() {
+            ReservationFileFS::NAME => Some(Box::new(ReservationFileFS::new(self))),
+            PrioritiesFileFS::NAME => Some(Box::new(PrioritiesFileFS::new(self))),
+            TasksFileFS::NAME => Some(Box::new(TasksFileFS::new(self))),
+            _ => None,
+        }
+    }
+
+    fn fs_from_inode<'a>(&'a mut self, inode: u64) -> Option<Box<dyn VirtualFS + 'a>> {
+        if inode & !INODE_DIR_FILE_MASK != self.inode() {
+            return None;
+        }
+
+        match inode & INODE_DIR_FILE_MASK {
+            0 => panic!("recursion"),
+            ReservationFileFS::INODE_OFFSET => Some(Box::new(ReservationFileFS::new(self))),
+            PrioritiesFileFS::INODE_OFFSET => Some(Box::new(PrioritiesFileFS::new(self))),
+            TasksFileFS::INODE_OFFSET => Some(Box::new(TasksFileFS::new(self))),
+            _ => None,
+        }
+    }
+
+    fn fs_inodes_in_dir(&self) -> impl Iterator<Item = u64> {
+        [
+            ReservationFileFS::INODE_OFFSET,
+            PrioritiesFileFS::INODE_OFFSET,
+            TasksFileFS::INODE_OFFSET,
+        ].into_iter().map(|offset| self.inode() + offset)
+    }
+}
+
+impl VirtualFile for ManagedCgroupDirFS<'_> {
+    fn inode(&self) -> u64 {
+        cgroup_to_dir_inode(self.cgroup)
+    }
+
+    fn attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.inode(),
+            size: 0,
+            blocks: 0,
+            atime: UNKNOWN_TIME,
+            mtime: UNKNOWN_TIME,
+            ctime: UNKNOWN_TIME,
+            crtime: UNKNOWN_TIME,
+            kind: FileType::Directory,
+            perm: 0o555,
+            nlink: 1,
+            uid: self.data.owner_uid,
+            gid: self.data.owner_gid,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
@@ -0,0 +1,71 @@
+use fuser::*;
+use crate::filesystem::utils::*;
+
+#[derive(Debug)]
+pub struct PrioritiesFileFS {
+    inode: u64,
+    priorities: String,
+    owner_uid: u32,
+    owner_gid: u32,
+}
+
+impl PrioritiesFileFS {
+    pub const NAME: &'static str = "priorities";
+    pub const INODE_OFFSET: u64 = 2;
+
+    pub fn new(cgroup_dir_fs: &mut super::ManagedCgroupDirFS<'_>) -> FileFS<Self> {
+        let data = cgroup_dir_fs.data;
+        let range = data.priority_range();
+
+        FileFS::new( Self {
+            inode: cgroup_dir_fs.inode() + Self::INODE_OFFSET,
+            priorities: format!("{} {}\n", range.min, range.max),
+            owner_uid: data.owner_uid,
+            owner_gid: data.owner_gid,
+        } )
+    }
+}
+
+impl FileFSInterface for PrioritiesFileFS {
+    fn read_size(&self) -> anyhow::Result<usize> {
+        Ok(self.priorities.len())
+    }
+
+    fn read_data(&self) -> anyhow::Result<&str> {
+        Ok(self.priorities.as_str())
+    }
+
+    fn write_data(&mut self, _data: &str) -> anyhow::Result<()> {
+        anyhow::bail!("Cannot write to PrioritiesFile")
+    }
+}
+
+impl VirtualFile for PrioritiesFileFS {
+    fn inode(&self) -> u64 {
+        self.inode
+    }
+
+    fn attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.inode,
+            size: 0,
+            blocks: 0,
+            atime: UNKNOWN_TIME,
+            mtime: UNKNOWN_TIME,
+            ctime: UNKNOWN_TIME,
+            crtime: UNKNOWN_TIME,
+            kind: FileType::RegularFile,
+            perm: 0o444,
+            nlink: 1,
+            uid: self.owner_uid,
+            gid: self.owner_gid,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
@@ -0,0 +1,84 @@
+use fuser::*;
+use crate::filesystem::utils::*;
+
+#[derive(Debug)]
+pub struct ReservationFileFS {
+    inode: u64,
+    reservation: String,
+    owner_uid: u32,
+    owner_gid: u32,
+}
+
+impl ReservationFileFS {
+    pub const NAME: &'static str = "reservation";
+    pub const INODE_OFFSET: u64 = 1;
+
+    pub fn new(cgroup_dir_fs: &mut super::ManagedCgroupDirFS<'_>) -> FileFS<Self> {
+        let data = cgroup_dir_fs.data;
+
+        // Elastic cgroups also show the runtime currently granted.
+        let mut reservation = match data.elasticity {
+            Some(elasticity) => format!("{} {} max={} weight={} granted={}",
+                data.reservation.runtime_us, data.reservation.period_us,
+                elasticity.max_runtime_us, elasticity.weight, data.granted_runtime_us),
+            None => format!("{} {}", data.reservation.runtime_us, data.reservation.period_us),
+        };
+
+        if let Some(cpus) = &data.cpus {
+            let cpus: Vec<_> = cpus.iter().map(|cpu| cpu.to_string()).collect();
+            reservation += &format!(" cpus={}", cpus.join(","));
+        }
+        reservation += "\n";
+
+        FileFS::new( Self {
+            inode: cgroup_dir_fs.inode() + Self::INODE_OFFSET,
+            reservation,
+            owner_uid: data.owner_uid,
+            owner_gid: data.owner_gid,
+        } )
+    }
+}
+
+impl FileFSInterface for ReservationFileFS {
+    fn read_size(&self) -> anyhow::Result<usize> {
+        Ok(self.reservation.len())
+    }
+
+    fn read_data(&self) -> anyhow::Result<&str> {
+        Ok(self.reservation.as_str())
+    }
+
+    fn write_data(&mut self, _data: &str) -> anyhow::Result<()> {
+        anyhow::bail!("Cannot write to ReservationFile")
+    }
+}
+
+impl VirtualFile for ReservationFileFS {
+    fn inode(&self) -> u64 {
+        self.inode
+    }
+
+    fn attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.inode,
+            size: 0,
+            blocks: 0,
+            atime: UNKNOWN_TIME,
+            mtime: UNKNOWN_TIME,
+            ctime: UNKNOWN_TIME,
+            crtime: UNKNOWN_TIME,
+            kind: FileType::RegularFile,
+            perm: 0o444,
+            nlink: 1,
+            uid: self.owner_uid,
+            gid: self.owner_gid,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
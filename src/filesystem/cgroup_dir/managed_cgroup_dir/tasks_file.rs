@@ -0,0 +1,81 @@
+use fuser::*;
+use crate::filesystem::utils::*;
+use crate::manager::CgroupData;
+
+#[derive(Debug)]
+pub struct TasksFileFS {
+    inode: u64,
+    tasks: String,
+    owner_uid: u32,
+    owner_gid: u32,
+}
+
+impl TasksFileFS {
+    pub const NAME: &'static str = "tasks";
+    pub const INODE_OFFSET: u64 = 3;
+
+    pub fn new(cgroup_dir_fs: &mut super::ManagedCgroupDirFS<'_>) -> FileFS<Self> {
+        let data = cgroup_dir_fs.data;
+
+        FileFS::new( Self {
+            inode: cgroup_dir_fs.inode() + Self::INODE_OFFSET,
+            tasks: Self::format(data),
+            owner_uid: data.owner_uid,
+            owner_gid: data.owner_gid,
+        } )
+    }
+
+    /// One task per line, followed by its worst-case response time.
+    fn format(data: &CgroupData) -> String {
+        data.tasks.iter()
+            .zip(data.response_times())
+            .map(|(task, response_time)| format!("{} {} {} {} {}\n",
+                task.wcet_us, task.period_us, task.deadline_us, task.priority,
+                response_time.map(|response_time| response_time.to_string()).unwrap_or("-".to_owned())))
+            .collect()
+    }
+}
+
+impl FileFSInterface for TasksFileFS {
+    fn read_size(&self) -> anyhow::Result<usize> {
+        Ok(self.tasks.len())
+    }
+
+    fn read_data(&self) -> anyhow::Result<&str> {
+        Ok(self.tasks.as_str())
+    }
+
+    fn write_data(&mut self, _data: &str) -> anyhow::Result<()> {
+        anyhow::bail!("Cannot write to TasksFile")
+    }
+}
+
+impl VirtualFile for TasksFileFS {
+    fn inode(&self) -> u64 {
+        self.inode
+    }
+
+    fn attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.inode,
+            size: 0,
+            blocks: 0,
+            atime: UNKNOWN_TIME,
+            mtime: UNKNOWN_TIME,
+            ctime: UNKNOWN_TIME,
+            crtime: UNKNOWN_TIME,
+            kind: FileType::RegularFile,
+            perm: 0o444,
+            nlink: 1,
+            uid: self.owner_uid,
+            gid: self.owner_gid,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
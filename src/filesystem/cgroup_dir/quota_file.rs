@@ -0,0 +1,75 @@
+use fuser::*;
+use crate::filesystem::utils::*;
+
+#[derive(Debug)]
+pub struct QuotaFileFS {
+    usages: String,
+}
+
+impl QuotaFileFS {
+    pub const NAME: &'static str = "quota";
+    pub const INODE: u64 = CGROUP_DIR_INODE + 4;
+
+    pub fn new(cgroup_dir_fs: &mut super::CgroupDirFS<'_>) -> FileFS<Self> {
+        let usages =
+            cgroup_dir_fs.manager.quota_usages().into_iter()
+            .map(|(subject, usage, quota)| {
+                let max_bandwidth =
+                    quota.and_then(|quota| quota.max_bandwidth)
+                    .map_or_else(|| "-".to_owned(), |max| format!("{max:.3}"));
+                let max_cgroups =
+                    quota.and_then(|quota| quota.max_cgroups)
+                    .map_or_else(|| "-".to_owned(), |max| format!("{max}"));
+
+                format!("{subject} bandwidth {:.3}/{max_bandwidth} cgroups {}/{max_cgroups}\n",
+                    usage.bandwidth, usage.cgroups)
+            })
+            .collect();
+
+        FileFS::new( Self { usages } )
+    }
+}
+
+impl FileFSInterface for QuotaFileFS {
+    fn read_size(&self) -> anyhow::Result<usize> {
+        Ok(self.usages.len())
+    }
+
+    fn read_data(&self) -> anyhow::Result<&str> {
+        Ok(self.usages.as_str())
+    }
+
+    fn write_data(&mut self, _data: &str) -> anyhow::Result<()> {
+        anyhow::bail!("Cannot write to QuotaFile")
+    }
+}
+
+impl VirtualFile for QuotaFileFS {
+    fn inode(&self) -> u64 {
+        Self::INODE
+    }
+
+    fn attr(&self) -> FileAttr {
+        FileAttr {
+            ino: Self::INODE,
+            size: 0,
+            blocks: 0,
+            atime: UNKNOWN_TIME,
+            mtime: UNKNOWN_TIME,
+            ctime: UNKNOWN_TIME,
+            crtime: UNKNOWN_TIME,
+            kind: FileType::RegularFile,
+            perm: 0o444,
+            nlink: 1,
+            uid: ROOT_UID,
+            gid: ROOT_GID,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
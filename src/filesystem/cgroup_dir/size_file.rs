@@ -0,0 +1,96 @@
+use fuser::*;
+use crate::manager::AnalysisTask;
+use crate::filesystem::utils::*;
+
+#[derive(Debug)]
+pub struct SizeFileFS<'a> {
+    cgroup_manager: &'a mut crate::manager::HCBSManager,
+    requester: crate::manager::Requester,
+    sizings: String,
+}
+
+impl<'a> SizeFileFS<'a> {
+    pub const NAME: &'static str = "size";
+    pub const INODE: u64 = CGROUP_DIR_INODE + 6;
+
+    pub fn new(cgroup_dir_fs: &'a mut super::CgroupDirFS<'_>) -> FileFS<Self> {
+        let sizings =
+            cgroup_dir_fs.manager.sizings()
+            .flat_map(|(name, reservations)| reservations.iter()
+                .map(move |reservation| format!("{name} {} {} {:.3}\n",
+                    reservation.runtime_us, reservation.period_us, reservation.bandwidth())))
+            .collect();
+
+        FileFS::new( Self {
+            sizings,
+            cgroup_manager: cgroup_dir_fs.manager,
+            requester: cgroup_dir_fs.requester,
+        } )
+    }
+
+    fn parse_request(data: &str) -> Option<(&str, bool, Vec<AnalysisTask>)> {
+        use nom::Parser as _;
+        use nom::bytes::complete::*;
+        use nom::character::complete::*;
+        use nom::combinator::*;
+        use nom::multi::*;
+        use nom::sequence::*;
+
+        // Malformed tasks must not be silently dropped.
+        terminated(
+            (
+                crate::filesystem::utils::parser::parse_cgroup_name,
+                map(opt(preceded(space1, tag("--create"))), |create| create.is_some()),
+                many1(preceded(space1, crate::filesystem::utils::parser::parse_analysis_task)),
+            ),
+            (multispace0, eof),
+        ).parse(data).map(|(_, res)| res).ok()
+    }
+}
+
+impl FileFSInterface for SizeFileFS<'_> {
+    fn read_size(&self) -> anyhow::Result<usize> {
+        Ok(self.sizings.len())
+    }
+
+    fn read_data(&self) -> anyhow::Result<&str> {
+        Ok(self.sizings.as_str())
+    }
+
+    fn write_data(&mut self, data: &str) -> anyhow::Result<()> {
+        let Some((name, create, tasks)) = Self::parse_request(data)
+            else { anyhow::bail!("Invalid request"); };
+
+        self.cgroup_manager.size_cgroup(name, tasks, create, &self.requester)
+    }
+}
+
+impl VirtualFile for SizeFileFS<'_> {
+    fn inode(&self) -> u64 {
+        Self::INODE
+    }
+
+    fn attr(&self) -> FileAttr {
+        FileAttr {
+            ino: Self::INODE,
+            size: 0,
+            blocks: 0,
+            atime: UNKNOWN_TIME,
+            mtime: UNKNOWN_TIME,
+            ctime: UNKNOWN_TIME,
+            crtime: UNKNOWN_TIME,
+            kind: FileType::RegularFile,
+            perm: 0o666,
+            nlink: 1,
+            uid: ROOT_UID,
+            gid: ROOT_GID,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
\ No newline at end of file
@@ -0,0 +1,81 @@
+use fuser::*;
+use crate::manager::AnalysisTask;
+use crate::filesystem::utils::*;
+
+#[derive(Debug)]
+pub struct TasksetFileFS<'a> {
+    cgroup_manager: &'a mut crate::manager::HCBSManager,
+    requester: crate::manager::Requester,
+}
+
+impl<'a> TasksetFileFS<'a> {
+    pub const NAME: &'static str = "taskset";
+    pub const INODE: u64 = CGROUP_DIR_INODE + 5;
+
+    pub fn new(cgroup_dir_fs: &'a mut super::CgroupDirFS<'_>) -> FileFS<Self> {
+        FileFS::new( Self {
+            cgroup_manager: cgroup_dir_fs.manager,
+            requester: cgroup_dir_fs.requester,
+        } )
+    }
+
+    fn parse_request(data: &str) -> Option<(&str, Vec<AnalysisTask>)> {
+        use nom::Parser as _;
+        use nom::character::complete::*;
+        use nom::combinator::*;
+        use nom::multi::*;
+        use nom::sequence::*;
+
+        // A malformed task must not be mistaken for the end of the task set.
+        terminated(
+            (
+                crate::filesystem::utils::parser::parse_cgroup_name,
+                many0(preceded(space1, crate::filesystem::utils::parser::parse_analysis_task)),
+            ),
+            (multispace0, eof),
+        ).parse(data).map(|(_, res)| res).ok()
+    }
+}
+
+impl FileFSInterface for TasksetFileFS<'_> {
+    fn read_size(&self) -> anyhow::Result<usize> { anyhow::bail!("Cannot read from TasksetFile") }
+
+    fn read_data(&self) -> anyhow::Result<&str> { anyhow::bail!("Cannot read from TasksetFile") }
+
+    fn write_data(&mut self, data: &str) -> anyhow::Result<()> {
+        let Some((name, tasks)) = Self::parse_request(data)
+            else { anyhow::bail!("Invalid request"); };
+
+        self.cgroup_manager.set_cgroup_tasks(name, tasks, &self.requester)
+    }
+}
+
+impl VirtualFile for TasksetFileFS<'_> {
+    fn inode(&self) -> u64 {
+        Self::INODE
+    }
+
+    fn attr(&self) -> FileAttr {
+        FileAttr {
+            ino: Self::INODE,
+            size: 0,
+            blocks: 0,
+            atime: UNKNOWN_TIME,
+            mtime: UNKNOWN_TIME,
+            ctime: UNKNOWN_TIME,
+            crtime: UNKNOWN_TIME,
+            kind: FileType::RegularFile,
+            perm: 0o666,
+            nlink: 1,
+            uid: ROOT_UID,
+            gid: ROOT_GID,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
\ No newline at end of file
@@ -0,0 +1,115 @@
+use fuser::*;
+use crate::manager::TransactionOp;
+use crate::filesystem::utils::*;
+
+#[derive(Debug)]
+pub struct TransactionFileFS<'a> {
+    cgroup_manager: &'a mut crate::manager::HCBSManager,
+    requester: crate::manager::Requester,
+    modes: String,
+}
+
+#[derive(Debug)]
+enum TransactionRequest<'a> {
+    Ops(Vec<TransactionOp>),
+    Mode(&'a str),
+}
+
+impl<'a> TransactionFileFS<'a> {
+    pub const NAME: &'static str = "transaction";
+    pub const INODE: u64 = CGROUP_DIR_INODE + 7;
+
+    pub fn new(cgroup_dir_fs: &'a mut super::CgroupDirFS<'_>) -> FileFS<Self> {
+        let manager = &cgroup_dir_fs.manager;
+        let modes =
+            manager.modes()
+            .map(|mode| {
+                let state = if manager.active_mode() == Some(mode.name.as_str()) { "active" } else { "inactive" };
+
+                format!("mode {} {state}\n", mode.name)
+            })
+            .collect();
+
+        FileFS::new( Self {
+            modes,
+            cgroup_manager: cgroup_dir_fs.manager,
+            requester: cgroup_dir_fs.requester,
+        } )
+    }
+
+    /// Either `mode <mode name>`, or one transaction step per line.
+    fn parse_request(data: &str) -> Option<TransactionRequest<'_>> {
+        use nom::Parser as _;
+        use nom::branch::*;
+        use nom::bytes::complete::*;
+        use nom::character::complete::*;
+        use nom::combinator::*;
+        use nom::multi::*;
+        use nom::sequence::*;
+
+        // Malformed steps must not be silently dropped.
+        terminated(
+            alt((
+                map(
+                    preceded((tag("mode"), space1), take_till1(|c: char| c.is_whitespace())),
+                    TransactionRequest::Mode,
+                ),
+                map(
+                    separated_list1((space0, line_ending, multispace0), crate::filesystem::utils::parser::parse_transaction_op),
+                    TransactionRequest::Ops,
+                ),
+            )),
+            (multispace0, eof),
+        ).parse(data).map(|(_, res)| res).ok()
+    }
+}
+
+impl FileFSInterface for TransactionFileFS<'_> {
+    fn read_size(&self) -> anyhow::Result<usize> {
+        Ok(self.modes.len())
+    }
+
+    fn read_data(&self) -> anyhow::Result<&str> {
+        Ok(self.modes.as_str())
+    }
+
+    fn write_data(&mut self, data: &str) -> anyhow::Result<()> {
+        let Some(request) = Self::parse_request(data)
+            else { anyhow::bail!("Invalid request"); };
+
+        match request {
+            TransactionRequest::Ops(ops) => self.cgroup_manager.run_transaction(ops, &self.requester),
+            TransactionRequest::Mode(mode) => self.cgroup_manager.switch_mode(mode, &self.requester),
+        }
+    }
+}
+
+impl VirtualFile for TransactionFileFS<'_> {
+    fn inode(&self) -> u64 {
+        Self::INODE
+    }
+
+    fn attr(&self) -> FileAttr {
+        FileAttr {
+            ino: Self::INODE,
+            size: 0,
+            blocks: 0,
+            atime: UNKNOWN_TIME,
+            mtime: UNKNOWN_TIME,
+            ctime: UNKNOWN_TIME,
+            crtime: UNKNOWN_TIME,
+            kind: FileType::RegularFile,
+            perm: 0o666,
+            nlink: 1,
+            uid: ROOT_UID,
+            gid: ROOT_GID,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
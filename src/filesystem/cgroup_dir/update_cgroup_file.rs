@@ -1,10 +1,12 @@
 use fuser::*;
-use crate::manager::Reservation;
+use crate::manager::{Elasticity, PriorityRange, Reservation};
 use crate::filesystem::utils::*;
+use hcbs_utils::prelude::CpuID;
 
 #[derive(Debug)]
 pub struct UpdateCgroupFileFS<'a> {
     cgroup_manager: &'a mut crate::manager::HCBSManager,
+    requester: crate::manager::Requester,
 }
 
 impl<'a> UpdateCgroupFileFS<'a> {
@@ -12,21 +14,32 @@ impl<'a> UpdateCgroupFileFS<'a> {
     pub const INODE: u64 = CGROUP_DIR_INODE + 3;
 
     pub fn new(cgroup_dir_fs: &'a mut super::CgroupDirFS<'_>) -> FileFS<Self> {
-        FileFS::new( Self { cgroup_manager: cgroup_dir_fs.manager } )
+        FileFS::new( Self {
+            cgroup_manager: cgroup_dir_fs.manager,
+            requester: cgroup_dir_fs.requester,
+        } )
     }
 
-    fn parse_request(data: &str) -> Option<(&str, Reservation)> {
+    fn parse_request(data: &str) -> Option<(&str, Reservation, Option<Option<PriorityRange>>, Option<Option<Elasticity>>, Option<Vec<CpuID>>)> {
         use nom::Parser as _;
         use nom::character::complete::*;
         use nom::combinator::*;
+        use nom::sequence::*;
 
-        map(
-            (
-                crate::filesystem::utils::parser::parse_cgroup_name,
-                space1,
-                crate::filesystem::utils::parser::parse_cgroup_alloc_request,
+        // Misordered or unknown options must not be silently dropped.
+        terminated(
+            map(
+                (
+                    crate::filesystem::utils::parser::parse_cgroup_name,
+                    space1,
+                    crate::filesystem::utils::parser::parse_cgroup_alloc_request,
+                    opt(preceded(space1, crate::filesystem::utils::parser::parse_priority_update)),
+                    opt(preceded(space1, crate::filesystem::utils::parser::parse_elasticity_update)),
+                    opt(preceded(space1, crate::filesystem::utils::parser::parse_cpus)),
+                ),
+                |(name, _, request, priorities, elasticity, cpus)| (name, request, priorities, elasticity, cpus)
             ),
-            |(name, _, request)| (name, request)
+            (multispace0, eof),
         ).parse(data).map(|(_, res)| res).ok()
     }
 }
@@ -37,10 +50,10 @@ impl FileFSInterface for UpdateCgroupFileFS<'_> {
     fn read_data(&self) -> anyhow::Result<&str> { anyhow::bail!("Cannot read from UpdateCgroupFile") }
 
     fn write_data(&mut self, data: &str) -> anyhow::Result<()> {
-        let Some((name, request)) = Self::parse_request(data)
+        let Some((name, request, priorities, elasticity, cpus)) = Self::parse_request(data)
             else { anyhow::bail!("Invalid request"); };
 
-        self.cgroup_manager.update_cgroup(name, request)
+        self.cgroup_manager.update_cgroup(name, request, priorities, elasticity, cpus, &self.requester)
     }
 }
 
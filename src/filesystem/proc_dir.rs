@@ -12,6 +12,7 @@ use pid_dir::*;
 pub struct ProcDirFS<'a> {
     active_procs: &'a HashMap<sysinfo::Pid, ProcessStats>,
     manager: &'a mut crate::manager::HCBSManager,
+    requester: crate::manager::Requester,
     root_fs_attr: FileAttr,
 }
 
@@ -24,6 +25,7 @@ impl<'a> ProcDirFS<'a> {
         DirFS::new( Self {
             active_procs: root_fs.active_procs,
             manager: root_fs.manager,
+            requester: root_fs.requester,
             root_fs_attr,
         } )
     }
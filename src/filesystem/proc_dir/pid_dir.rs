@@ -4,9 +4,15 @@ use crate::ProcessStats;
 
 mod cgroup_file;
 mod sched_policy_file;
+mod placement_file;
+mod release_file;
+mod timing_file;
 
 use cgroup_file::*;
 use sched_policy_file::*;
+use placement_file::*;
+use release_file::*;
+use timing_file::*;
 
 #[derive(Debug)]
 pub struct PidDirFS<'a> {
@@ -14,6 +20,7 @@ pub struct PidDirFS<'a> {
     stats: &'a ProcessStats,
     name: String,
     manager: &'a mut crate::manager::HCBSManager,
+    requester: crate::manager::Requester,
     proc_dir_attr: FileAttr,
 }
 
@@ -32,6 +39,7 @@ impl<'a> PidDirFS<'a> {
             stats,
             name: format!("{pid}"),
             manager: proc_dir.manager,
+            requester: proc_dir.requester,
             proc_dir_attr: proc_dir_attr,
         }))
     }
@@ -50,6 +58,7 @@ impl<'a> PidDirFS<'a> {
             stats,
             name: format!("{pid}"),
             manager: proc_dir.manager,
+            requester: proc_dir.requester,
             proc_dir_attr: proc_dir_attr,
         }))
     }
@@ -64,6 +73,9 @@ impl DirFSInterface for PidDirFS<'_> {
         match name.to_str().unwrap() {
             CgroupFileFS::NAME => Some(Box::new(CgroupFileFS::new(self))),
             SchedPolicyFileFS::NAME => Some(Box::new(SchedPolicyFileFS::new(self))),
+            PlacementFileFS::NAME => Some(Box::new(PlacementFileFS::new(self))),
+            ReleaseFileFS::NAME => Some(Box::new(ReleaseFileFS::new(self))),
+            TimingFileFS::NAME => Some(Box::new(TimingFileFS::new(self))),
             _ => None,
         }
     }
@@ -77,6 +89,9 @@ impl DirFSInterface for PidDirFS<'_> {
             0 => panic!("recursion"),
             CgroupFileFS::INODE_OFFSET => Some(Box::new(CgroupFileFS::new(self))),
             SchedPolicyFileFS::INODE_OFFSET => Some(Box::new(SchedPolicyFileFS::new(self))),
+            PlacementFileFS::INODE_OFFSET => Some(Box::new(PlacementFileFS::new(self))),
+            ReleaseFileFS::INODE_OFFSET => Some(Box::new(ReleaseFileFS::new(self))),
+            TimingFileFS::INODE_OFFSET => Some(Box::new(TimingFileFS::new(self))),
             _ => None,
         }
     }
@@ -85,6 +100,9 @@ impl DirFSInterface for PidDirFS<'_> {
         [
             CgroupFileFS::INODE_OFFSET,
             SchedPolicyFileFS::INODE_OFFSET,
+            PlacementFileFS::INODE_OFFSET,
+            ReleaseFileFS::INODE_OFFSET,
+            TimingFileFS::INODE_OFFSET,
         ].into_iter().map(|offset| self.inode() + offset)
     }
 }
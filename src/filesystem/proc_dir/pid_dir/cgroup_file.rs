@@ -1,6 +1,8 @@
 use fuser::*;
 use hcbs_utils::prelude::*;
 use crate::filesystem::utils::*;
+use crate::ops::get_pid_cgroup;
+use crate::manager::TaskScope;
 use crate::ProcessStats;
 
 pub struct CgroupFileFS<'a> {
@@ -8,6 +10,7 @@ pub struct CgroupFileFS<'a> {
     stats: &'a ProcessStats,
     cgroup: Option<String>,
     manager: &'a mut crate::manager::HCBSManager,
+    requester: crate::manager::Requester,
 }
 
 impl<'a> CgroupFileFS<'a> {
@@ -23,12 +26,17 @@ impl<'a> CgroupFileFS<'a> {
             stats: pid_dir_fs.stats,
             cgroup,
             manager: pid_dir_fs.manager,
+            requester: pid_dir_fs.requester,
         } )
     }
 
-    fn parse_request(data: &str) -> Option<&str> {
-        crate::filesystem::utils::
-            parser::parse_cgroup_name(data).map(|(_, res)| res).ok()
+    fn parse_request(data: &str) -> Option<(&str, TaskScope)> {
+        use nom::Parser as _;
+
+        (
+            crate::filesystem::utils::parser::parse_cgroup_name,
+            crate::filesystem::utils::parser::parse_task_scope,
+        ).parse(data).map(|(_, res)| res).ok()
     }
 }
 
@@ -46,10 +54,10 @@ impl FileFSInterface for CgroupFileFS<'_> {
     }
 
     fn write_data(&mut self, data: &str) -> anyhow::Result<()> {
-        let Some(name) = Self::parse_request(data)
+        let Some((name, scope)) = Self::parse_request(data)
             else { anyhow::bail!("Invalid request"); };
 
-        self.manager.assign_cgroup_to_process(self.pid.as_u32(), name)
+        self.manager.assign_cgroup_to_process(self.pid.as_u32(), name, scope, &self.requester)
     }
 }
 
@@ -0,0 +1,104 @@
+use fuser::*;
+use hcbs_utils::prelude::*;
+use crate::filesystem::utils::*;
+use crate::ops::{get_pid_cgroup, get_sched_policy};
+use crate::manager::PolicyRequest;
+use crate::ProcessStats;
+
+pub struct PlacementFileFS<'a> {
+    pid: sysinfo::Pid,
+    stats: &'a ProcessStats,
+    placement: Option<String>,
+    manager: &'a mut crate::manager::HCBSManager,
+    requester: crate::manager::Requester,
+}
+
+impl<'a> PlacementFileFS<'a> {
+    pub const NAME: &'static str = "placement";
+    pub const INODE_OFFSET: u64 = 4;
+
+    pub fn new(pid_dir_fs: &'a mut super::PidDirFS<'_>) -> FileFS<Self> {
+        let pid = pid_dir_fs.pid.as_u32();
+        let placement = get_pid_cgroup(pid)
+            .and_then(|cgroup| {
+                let policy = get_sched_policy(pid)?;
+                let cgroup_data = pid_dir_fs.manager.managed_cgroup(&cgroup);
+
+                Ok(format!("{cgroup} {}\n", sched_policy_to_string_relative(&policy, cgroup_data)))
+            }).ok();
+
+        FileFS::new(Self {
+            pid: pid_dir_fs.pid,
+            stats: pid_dir_fs.stats,
+            placement,
+            manager: pid_dir_fs.manager,
+            requester: pid_dir_fs.requester,
+        } )
+    }
+
+    fn parse_request(data: &str) -> Option<(&str, PolicyRequest)> {
+        use nom::Parser as _;
+        use nom::character::complete::*;
+        use nom::combinator::*;
+
+        map(
+            (
+                crate::filesystem::utils::parser::parse_cgroup_name,
+                space1,
+                crate::filesystem::utils::parser::parse_policy_request,
+            ),
+            |(name, _, policy)| (name, policy)
+        ).parse(data).map(|(_, res)| res).ok()
+    }
+}
+
+impl FileFSInterface for PlacementFileFS<'_> {
+    fn read_size(&self) -> anyhow::Result<usize> {
+        self.placement.as_ref()
+            .map(|str| str.len())
+            .ok_or_else(|| anyhow::anyhow!("Placement not found") )
+    }
+
+    fn read_data(&self) -> anyhow::Result<&str> {
+        self.placement.as_ref()
+            .map(|str| str.as_str())
+            .ok_or_else(|| anyhow::anyhow!("Placement not found") )
+    }
+
+    fn write_data(&mut self, data: &str) -> anyhow::Result<()> {
+        let Some((name, policy)) = Self::parse_request(data)
+            else { anyhow::bail!("Invalid request"); };
+
+        self.manager.place_process(self.pid.as_u32(), name, policy, &self.requester)
+    }
+}
+
+impl VirtualFile for PlacementFileFS<'_> {
+    fn inode(&self) -> u64 {
+        pid_to_dir_inode(self.pid) + Self::INODE_OFFSET
+    }
+
+    fn attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.inode(),
+            size: 0,
+            blocks: 0,
+            atime: self.stats.crtime,
+            mtime: self.stats.crtime,
+            ctime: self.stats.crtime,
+            crtime: self.stats.crtime,
+            kind: FileType::RegularFile,
+            perm: 0o664,
+            nlink: 1,
+            uid: *self.stats.uid,
+            gid: *self.stats.gid,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
@@ -0,0 +1,64 @@
+use fuser::*;
+use crate::filesystem::utils::*;
+use crate::ProcessStats;
+
+pub struct ReleaseFileFS<'a> {
+    pid: sysinfo::Pid,
+    stats: &'a ProcessStats,
+    manager: &'a mut crate::manager::HCBSManager,
+    requester: crate::manager::Requester,
+}
+
+impl<'a> ReleaseFileFS<'a> {
+    pub const NAME: &'static str = "release";
+    pub const INODE_OFFSET: u64 = 5;
+
+    pub fn new(pid_dir_fs: &'a mut super::PidDirFS<'_>) -> FileFS<Self> {
+        FileFS::new(Self {
+            pid: pid_dir_fs.pid,
+            stats: pid_dir_fs.stats,
+            manager: pid_dir_fs.manager,
+            requester: pid_dir_fs.requester,
+        } )
+    }
+}
+
+impl FileFSInterface for ReleaseFileFS<'_> {
+    fn read_size(&self) -> anyhow::Result<usize> { anyhow::bail!("Cannot read from ReleaseFile") }
+
+    fn read_data(&self) -> anyhow::Result<&str> { anyhow::bail!("Cannot read from ReleaseFile") }
+
+    fn write_data(&mut self, _data: &str) -> anyhow::Result<()> {
+        self.manager.release_process(self.pid.as_u32(), &self.requester)
+    }
+}
+
+impl VirtualFile for ReleaseFileFS<'_> {
+    fn inode(&self) -> u64 {
+        pid_to_dir_inode(self.pid) + Self::INODE_OFFSET
+    }
+
+    fn attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.inode(),
+            size: 0,
+            blocks: 0,
+            atime: self.stats.crtime,
+            mtime: self.stats.crtime,
+            ctime: self.stats.crtime,
+            crtime: self.stats.crtime,
+            kind: FileType::RegularFile,
+            perm: 0o220,
+            nlink: 1,
+            uid: *self.stats.uid,
+            gid: *self.stats.gid,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
@@ -1,7 +1,8 @@
 use fuser::*;
 use hcbs_utils::prelude::*;
-use nom::sequence::delimited;
 use crate::filesystem::utils::*;
+use crate::ops::{get_pid_cgroup, get_sched_policy};
+use crate::manager::{PolicyRequest, TaskScope};
 use crate::ProcessStats;
 
 pub struct SchedPolicyFileFS<'a> {
@@ -9,6 +10,7 @@ pub struct SchedPolicyFileFS<'a> {
     stats: &'a ProcessStats,
     policy: Option<(SchedPolicy, String)>,
     manager: &'a mut crate::manager::HCBSManager,
+    requester: crate::manager::Requester,
 }
 
 impl<'a> SchedPolicyFileFS<'a> {
@@ -16,18 +18,14 @@ impl<'a> SchedPolicyFileFS<'a> {
     pub const INODE_OFFSET: u64 = 3;
 
     pub fn new(pid_dir_fs: &'a mut super::PidDirFS<'_>) -> FileFS<Self> {
-        let policy = get_sched_policy(pid_dir_fs.pid.as_u32())
+        let pid = pid_dir_fs.pid.as_u32();
+        let policy = get_sched_policy(pid)
             .map(|policy| {
-                use SchedPolicy::*;
+                let cgroup_data =
+                    get_pid_cgroup(pid).ok()
+                    .and_then(|cgroup| pid_dir_fs.manager.managed_cgroup(&cgroup));
 
-                let str = match policy {
-                    OTHER { .. } => format!("SCHED_OTHER\n"),
-                    BATCH { .. } => format!("SCHED_BATCH\n"),
-                    IDLE => format!("SCHED_IDLE\n"),
-                    FIFO(prio) => format!("SCHED_FIFO({prio})\n"),
-                    RR(prio) => format!("SCHED_RR({prio})\n"),
-                    DEADLINE { .. } => format!("SCHED_DEADLINE\n"),
-                };
+                let str = format!("{}\n", sched_policy_to_string_relative(&policy, cgroup_data));
 
                 (policy, str)
             }).ok();
@@ -37,45 +35,17 @@ impl<'a> SchedPolicyFileFS<'a> {
             stats: pid_dir_fs.stats,
             policy,
             manager: pid_dir_fs.manager,
+            requester: pid_dir_fs.requester,
         } )
     }
 
-    fn parse_request(data: &str) -> Option<SchedPolicy> {
+    fn parse_request(data: &str) -> Option<(PolicyRequest, TaskScope)> {
         use nom::Parser as _;
-        use nom::branch::*;
-        use nom::bytes::complete::*;
-        use nom::combinator::*;
-
-        alt((
-            value(
-                SchedPolicy::other(),
-                tag("SCHED_OTHER"),
-            ),
-            map_res(
-                (
-                    tag("SCHED_FIFO"),
-                    delimited(
-                        tag("("),
-                        crate::filesystem::utils::
-                            parser::parse_u64,
-                        tag(")")
-                    )
-                ),
-                |(_, prio)| prio.try_into().map(|prio| SchedPolicy::FIFO(prio))
-            ),
-            map_res(
-                (
-                    tag("SCHED_RR"),
-                    delimited(
-                        tag("("),
-                        crate::filesystem::utils::
-                            parser::parse_u64,
-                        tag(")")
-                    )
-                ),
-                |(_, prio)| prio.try_into().map(|prio| SchedPolicy::RR(prio))
-            )
-        )).parse(data).map(|(_, policy)| policy).ok()
+
+        (
+            crate::filesystem::utils::parser::parse_policy_request,
+            crate::filesystem::utils::parser::parse_task_scope,
+        ).parse(data).map(|(_, res)| res).ok()
     }
 }
 
@@ -95,10 +65,10 @@ impl FileFSInterface for SchedPolicyFileFS<'_> {
     }
 
     fn write_data(&mut self, data: &str) -> anyhow::Result<()> {
-        let Some(policy) = Self::parse_request(data)
+        let Some((policy, scope)) = Self::parse_request(data)
             else { anyhow::bail!("Invalid request"); };
 
-        self.manager.set_process_sched_policy(self.pid.as_u32(), policy)
+        self.manager.set_process_sched_policy(self.pid.as_u32(), policy, scope, &self.requester)
     }
 }
 
@@ -0,0 +1,89 @@
+use fuser::*;
+use crate::filesystem::utils::*;
+use crate::manager::Timing;
+use crate::ProcessStats;
+
+pub struct TimingFileFS<'a> {
+    pid: sysinfo::Pid,
+    stats: &'a ProcessStats,
+    timing: String,
+    manager: &'a mut crate::manager::HCBSManager,
+    requester: crate::manager::Requester,
+}
+
+impl<'a> TimingFileFS<'a> {
+    pub const NAME: &'static str = "timing";
+    pub const INODE_OFFSET: u64 = 6;
+
+    pub fn new(pid_dir_fs: &'a mut super::PidDirFS<'_>) -> FileFS<Self> {
+        let timing =
+            pid_dir_fs.manager.process_timing(pid_dir_fs.pid.as_u32())
+            .map(|timing| format!("{} {} {}\n",
+                timing.period_us,
+                timing.deadline_us(),
+                timing.wcet_us.map(|wcet_us| wcet_us.to_string()).unwrap_or("-".to_owned())))
+            .unwrap_or_default();
+
+        FileFS::new(Self {
+            pid: pid_dir_fs.pid,
+            stats: pid_dir_fs.stats,
+            timing,
+            manager: pid_dir_fs.manager,
+            requester: pid_dir_fs.requester,
+        } )
+    }
+
+    fn parse_request(data: &str) -> Option<Option<Timing>> {
+        use nom::Parser as _;
+
+        crate::filesystem::utils::parser::parse_timing
+            .parse(data).map(|(_, res)| res).ok()
+    }
+}
+
+impl FileFSInterface for TimingFileFS<'_> {
+    fn read_size(&self) -> anyhow::Result<usize> {
+        Ok(self.timing.len())
+    }
+
+    fn read_data(&self) -> anyhow::Result<&str> {
+        Ok(self.timing.as_str())
+    }
+
+    fn write_data(&mut self, data: &str) -> anyhow::Result<()> {
+        let Some(timing) = Self::parse_request(data)
+            else { anyhow::bail!("Invalid request"); };
+
+        self.manager.set_process_timing(self.pid.as_u32(), timing, &self.requester)
+    }
+}
+
+impl VirtualFile for TimingFileFS<'_> {
+    fn inode(&self) -> u64 {
+        pid_to_dir_inode(self.pid) + Self::INODE_OFFSET
+    }
+
+    fn attr(&self) -> FileAttr {
+        FileAttr {
+            ino: self.inode(),
+            size: 0,
+            blocks: 0,
+            atime: self.stats.crtime,
+            mtime: self.stats.crtime,
+            ctime: self.stats.crtime,
+            crtime: self.stats.crtime,
+            kind: FileType::RegularFile,
+            perm: 0o664,
+            nlink: 1,
+            uid: *self.stats.uid,
+            gid: *self.stats.gid,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
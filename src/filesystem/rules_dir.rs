@@ -0,0 +1,93 @@
+use fuser::*;
+use crate::filesystem::utils::*;
+
+mod add_rule_file;
+mod delete_rule_file;
+mod list_rules_file;
+
+use add_rule_file::*;
+use delete_rule_file::*;
+use list_rules_file::*;
+
+#[derive(Debug)]
+pub struct RulesDirFS<'a> {
+    manager: &'a mut crate::manager::HCBSManager,
+    requester: crate::manager::Requester,
+    root_fs_attr: FileAttr,
+}
+
+impl<'a> RulesDirFS<'a> {
+    pub const NAME: &'static str = "rules";
+
+    pub fn new(root_fs: &'a mut super::RootFS<'_>) -> DirFS<Self> {
+        let root_fs_attr = root_fs.attr();
+
+        DirFS::new( Self {
+            manager: root_fs.manager,
+            requester: root_fs.requester,
+            root_fs_attr,
+        } )
+    }
+}
+
+impl DirFSInterface for RulesDirFS<'_> {
+    fn parent_attr(&self) -> Option<FileAttr> {
+        Some(self.root_fs_attr)
+    }
+
+    fn fs_from_file_name<'a>(&'a mut self, name: &std::ffi::OsStr) -> Option<Box<dyn VirtualFS + 'a>> {
+        match name.to_str().unwrap() {
+            AddRuleFileFS::NAME => Some(Box::new(AddRuleFileFS::new(self))),
+            DeleteRuleFileFS::NAME => Some(Box::new(DeleteRuleFileFS::new(self))),
+            ListRulesFileFS::NAME => Some(Box::new(ListRulesFileFS::new(self))),
+            _ => None,
+        }
+    }
+
+    fn fs_from_inode<'a>(&'a mut self, inode: u64) -> Option<Box<dyn VirtualFS + 'a>> {
+        match inode {
+            AddRuleFileFS::INODE => Some(Box::new(AddRuleFileFS::new(self))),
+            DeleteRuleFileFS::INODE => Some(Box::new(DeleteRuleFileFS::new(self))),
+            ListRulesFileFS::INODE => Some(Box::new(ListRulesFileFS::new(self))),
+            _ => None,
+        }
+    }
+
+    fn fs_inodes_in_dir(&self) -> impl Iterator<Item = u64> {
+        [
+            AddRuleFileFS::INODE,
+            DeleteRuleFileFS::INODE,
+            ListRulesFileFS::INODE,
+        ].into_iter()
+    }
+}
+
+impl VirtualFile for RulesDirFS<'_> {
+    fn inode(&self) -> u64 {
+        RULES_DIR_INODE
+    }
+
+    fn attr(&self) -> FileAttr {
+        FileAttr {
+            ino: RULES_DIR_INODE,
+            size: 0,
+            blocks: 0,
+            atime: UNKNOWN_TIME,
+            mtime: UNKNOWN_TIME,
+            ctime: UNKNOWN_TIME,
+            crtime: UNKNOWN_TIME,
+            kind: FileType::Directory,
+            perm: 0o775,
+            nlink: 1,
+            uid: ROOT_UID,
+            gid: ROOT_GID,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
\ No newline at end of file
@@ -0,0 +1,65 @@
+use fuser::*;
+use crate::filesystem::utils::*;
+
+#[derive(Debug)]
+pub struct DeleteRuleFileFS<'a> {
+    manager: &'a mut crate::manager::HCBSManager,
+    requester: crate::manager::Requester,
+}
+
+impl<'a> DeleteRuleFileFS<'a> {
+    pub const NAME: &'static str = "delete";
+    pub const INODE: u64 = RULES_DIR_INODE + 2;
+
+    pub fn new(rules_dir_fs: &'a mut super::RulesDirFS<'_>) -> FileFS<Self> {
+        FileFS::new( Self { manager: rules_dir_fs.manager, requester: rules_dir_fs.requester } )
+    }
+
+    fn parse_request(data: &str) -> Option<&str> {
+        crate::filesystem::utils::
+            parser::parse_identifier(data).map(|(_, res)| res).ok()
+    }
+}
+
+impl FileFSInterface for DeleteRuleFileFS<'_> {
+    fn read_size(&self) -> anyhow::Result<usize> { anyhow::bail!("Cannot read from DeleteRuleFile") }
+
+    fn read_data(&self) -> anyhow::Result<&str> { anyhow::bail!("Cannot read from DeleteRuleFile") }
+
+    fn write_data(&mut self, data: &str) -> anyhow::Result<()> {
+        let Some(name) = Self::parse_request(data)
+            else { anyhow::bail!("Invalid request"); };
+
+        self.manager.remove_rule(name, &self.requester)
+    }
+}
+
+impl VirtualFile for DeleteRuleFileFS<'_> {
+    fn inode(&self) -> u64 {
+        Self::INODE
+    }
+
+    fn attr(&self) -> FileAttr {
+        FileAttr {
+            ino: Self::INODE,
+            size: 0,
+            blocks: 0,
+            atime: UNKNOWN_TIME,
+            mtime: UNKNOWN_TIME,
+            ctime: UNKNOWN_TIME,
+            crtime: UNKNOWN_TIME,
+            kind: FileType::RegularFile,
+            perm: 0o644,
+            nlink: 1,
+            uid: ROOT_UID,
+            gid: ROOT_GID,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
@@ -0,0 +1,73 @@
+use fuser::*;
+use crate::filesystem::utils::*;
+
+#[derive(Debug)]
+pub struct ListRulesFileFS {
+    rules: String,
+}
+
+impl ListRulesFileFS {
+    pub const NAME: &'static str = "list";
+    pub const INODE: u64 = RULES_DIR_INODE + 3;
+
+    pub fn new(rules_dir_fs: &mut super::RulesDirFS<'_>) -> FileFS<Self> {
+        let rules =
+            rules_dir_fs.manager.rules()
+            .map(|rule| {
+                let matchers =
+                    rule.matchers.iter()
+                    .map(|matcher| matcher.to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+
+                format!("{} {matchers} -> {} {}\n", rule.name, rule.cgroup, sched_policy_to_string(&rule.policy))
+            })
+            .collect();
+
+        FileFS::new( Self { rules } )
+    }
+}
+
+impl FileFSInterface for ListRulesFileFS {
+    fn read_size(&self) -> anyhow::Result<usize> {
+        Ok(self.rules.len())
+    }
+
+    fn read_data(&self) -> anyhow::Result<&str> {
+        Ok(self.rules.as_str())
+    }
+
+    fn write_data(&mut self, _data: &str) -> anyhow::Result<()> {
+        anyhow::bail!("Cannot write to ListRulesFile")
+    }
+}
+
+impl VirtualFile for ListRulesFileFS {
+    fn inode(&self) -> u64 {
+        Self::INODE
+    }
+
+    fn attr(&self) -> FileAttr {
+        FileAttr {
+            ino: Self::INODE,
+            size: 0,
+            blocks: 0,
+            atime: UNKNOWN_TIME,
+            mtime: UNKNOWN_TIME,
+            ctime: UNKNOWN_TIME,
+            crtime: UNKNOWN_TIME,
+            kind: FileType::RegularFile,
+            perm: 0o444,
+            nlink: 1,
+            uid: ROOT_UID,
+            gid: ROOT_GID,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    fn name(&self) -> &str {
+        Self::NAME
+    }
+}
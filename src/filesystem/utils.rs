@@ -14,6 +14,38 @@ pub use file_fs::{
     FileFSInterface,
 };
 
+/// Shows the policy, followed by its relative priority if the process' cgroup
+/// has a priority band containing it, e.g. `SCHED_FIFO(45) rel:3`.
+pub fn sched_policy_to_string_relative(
+    policy: &hcbs_utils::prelude::SchedPolicy,
+    cgroup_data: Option<&crate::manager::CgroupData>,
+) -> String {
+    use hcbs_utils::prelude::SchedPolicy::*;
+
+    let relative = match (policy, cgroup_data) {
+        (FIFO(prio) | RR(prio), Some(data)) => data.relative_priority(*prio),
+        _ => None,
+    };
+
+    match relative {
+        Some(relative) => format!("{} rel:{relative}", sched_policy_to_string(policy)),
+        None => sched_policy_to_string(policy),
+    }
+}
+
+pub fn sched_policy_to_string(policy: &hcbs_utils::prelude::SchedPolicy) -> String {
+    use hcbs_utils::prelude::SchedPolicy::*;
+
+    match policy {
+        OTHER { .. } => format!("SCHED_OTHER"),
+        BATCH { .. } => format!("SCHED_BATCH"),
+        IDLE => format!("SCHED_IDLE"),
+        FIFO(prio) => format!("SCHED_FIFO({prio})"),
+        RR(prio) => format!("SCHED_RR({prio})"),
+        DEADLINE { .. } => format!("SCHED_DEADLINE"),
+    }
+}
+
 pub trait VirtualFS: VirtualFile + Filesystem { }
 
 pub trait VirtualFile {
@@ -35,11 +67,20 @@ pub const ROOT_GID: u32 = 0;
 ///
 /// Dir Types:
 /// 0   RootFS
+///     Dir Files: 1 Root Dir, 2 Capabilities, 3 Admission
 /// 1   Proc
 ///     Dir Id == PID
+///         Dir Files: 2 Cgroup, 3 Sched Policy, 4 Placement, 5 Release, 6 Timing
 /// 2   CGroup
-///     Dir Id == CGroup Name Hash 32-bit (?)
-/// 3   -
+///     Dir Id == 0 for the cgroup folder,
+///         Dir Files: 1 Create, 2 Delete, 3 Update, 4 Quota, 5 Taskset, 6 Size,
+///                    7 Transaction
+///         7 is the last file the 3 bits can address: new cgroup files need a
+///         new folder or a wider file mask.
+///     Dir Id == CGroup Name Hash for the managed cgroups' folders
+///         Dir Files: 1 Reservation, 2 Priorities, 3 Tasks
+/// 3   Rules
+///     Dir Files: 1 Add, 2 Delete, 3 List
 
 pub const INODE_DIR_TYPE_SHIFT: u64 = 62;
 pub const INODE_DIR_TYPE_MASK: u64 = 3 << INODE_DIR_TYPE_SHIFT;
@@ -47,6 +88,7 @@ pub const INODE_DIR_TYPE_MASK: u64 = 3 << INODE_DIR_TYPE_SHIFT;
 pub const ROOT_INODE_DIR_TYPE: u64 = 0 << INODE_DIR_TYPE_SHIFT;
 pub const PROC_INODE_DIR_TYPE: u64 = 1 << INODE_DIR_TYPE_SHIFT;
 pub const CGROUP_INODE_DIR_TYPE: u64 = 2 << INODE_DIR_TYPE_SHIFT;
+pub const RULES_INODE_DIR_TYPE: u64 = 3 << INODE_DIR_TYPE_SHIFT;
 
 pub const INODE_DIR_ID_SHIFT: u64 = 3;
 pub const INODE_DIR_ID_MASK: u64 = ((1 << INODE_DIR_TYPE_SHIFT) - 1) & !INODE_DIR_FILE_MASK;
@@ -57,6 +99,7 @@ pub const INODE_DIR_FILE_MASK: u64 = (1 << INODE_DIR_ID_SHIFT) - 1;
 pub const ROOT_DIR_INODE: u64 = ROOT_INODE_DIR_TYPE | 1;
 pub const PROC_DIR_INODE: u64 = PROC_INODE_DIR_TYPE;
 pub const CGROUP_DIR_INODE: u64 = CGROUP_INODE_DIR_TYPE;
+pub const RULES_DIR_INODE: u64 = RULES_INODE_DIR_TYPE;
 
 pub fn inode_is_pid(inode: u64) -> bool {
     (inode & INODE_DIR_TYPE_MASK) == PROC_INODE_DIR_TYPE
@@ -73,4 +116,22 @@ pub fn inode_to_pid_dir(inode: u64) -> Option<sysinfo::Pid> {
 
 pub fn pid_to_dir_inode(pid: sysinfo::Pid) -> u64 {
     (pid.as_u32() as u64) << INODE_DIR_ID_SHIFT | PROC_INODE_DIR_TYPE
-}
\ No newline at end of file
+}
+
+/// Managed cgroups' folders are named after the cgroup, with '/' replaced by
+/// '.' as folder names cannot contain slashes.
+pub fn cgroup_to_dir_name(name: &str) -> String {
+    name.replace('/', ".")
+}
+
+pub fn cgroup_to_dir_inode(name: &str) -> u64 {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    name.hash(&mut hasher);
+
+    // Dir Id 0 is the cgroup folder itself.
+    let id = hasher.finish() % (INODE_DIR_ID_MASK >> INODE_DIR_ID_SHIFT) + 1;
+
+    id << INODE_DIR_ID_SHIFT | CGROUP_INODE_DIR_TYPE
+}
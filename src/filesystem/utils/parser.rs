@@ -38,6 +38,206 @@ pub fn parse_cgroup_alloc_request(data: &str) -> nom::IResult<&str, crate::manag
     ).parse(data)
 }
 
+pub fn parse_eviction_policy(data: &str) -> nom::IResult<&str, crate::manager::EvictionPolicy, ()> {
+    use nom::Parser as _;
+    use nom::branch::*;
+    use nom::bytes::complete::*;
+    use nom::combinator::*;
+
+    use crate::manager::EvictionPolicy;
+
+    alt((
+        value(EvictionPolicy::Kill, tag("kill")),
+        value(EvictionPolicy::Demote, tag("demote")),
+        value(EvictionPolicy::Original, tag("original")),
+        value(EvictionPolicy::Leave, tag("leave")),
+    )).parse(data)
+}
+
+/// Parses a transaction step, one of:
+/// - `create <cgroup name> <runtime us> <period us> [prio=<min>-<max>] [max=<runtime us> [weight=<n>]] [cpus=<cpu list>]`
+/// - `update <cgroup name> <runtime us> <period us> [prio=<min>-<max>|none] [max=<runtime us> [weight=<n>]|max=none] [cpus=<cpu list>]`
+/// - `delete <cgroup name> [kill|demote|original]`
+pub fn parse_transaction_op(data: &str) -> nom::IResult<&str, crate::manager::TransactionOp, ()> {
+    use nom::Parser as _;
+    use nom::branch::*;
+    use nom::bytes::complete::*;
+    use nom::character::complete::*;
+    use nom::combinator::*;
+    use nom::sequence::*;
+
+    use crate::manager::TransactionOp;
+
+    let reservation = || (
+        parse_cgroup_name,
+        preceded(space1, parse_cgroup_alloc_request),
+        opt(preceded(space1, parse_priority_range)),
+        opt(preceded(space1, parse_elasticity)),
+        opt(preceded(space1, parse_cpus)),
+    );
+
+    // Each step must end with its line, so that misordered or unknown
+    // options are not silently dropped.
+    terminated(alt((
+        map(
+            preceded((tag("create"), space1), reservation()),
+            |(name, request, priorities, elasticity, cpus)| TransactionOp::Create {
+                name: name.to_owned(), request, priorities, elasticity, cpus, tasks: None,
+            }
+        ),
+        map(
+            preceded(
+                (tag("update"), space1),
+                (
+                    parse_cgroup_name,
+                    preceded(space1, parse_cgroup_alloc_request),
+                    opt(preceded(space1, parse_priority_update)),
+                    opt(preceded(space1, parse_elasticity_update)),
+                    opt(preceded(space1, parse_cpus)),
+                ),
+            ),
+            |(name, request, priorities, elasticity, cpus)| TransactionOp::Update {
+                name: name.to_owned(), request, priorities, elasticity, cpus, tasks: None,
+            }
+        ),
+        map(
+            preceded(
+                (tag("delete"), space1),
+                (parse_cgroup_name, opt(preceded(space1, parse_eviction_policy))),
+            ),
+            |(name, eviction)| TransactionOp::Delete { name: name.to_owned(), eviction }
+        ),
+    )), peek((space0, alt((line_ending, eof))))).parse(data)
+}
+
+/// Parses a priority range, e.g. `prio=10-20`.
+pub fn parse_priority_range(data: &str) -> nom::IResult<&str, crate::manager::PriorityRange, ()> {
+    use nom::Parser as _;
+    use nom::bytes::complete::*;
+    use nom::combinator::*;
+    use nom::sequence::*;
+
+    map_res(
+        preceded(
+            tag("prio="),
+            separated_pair(parse_u64, tag("-"), parse_u64),
+        ),
+        |(min, max)| -> anyhow::Result<_> {
+            crate::manager::PriorityRange::new(min.try_into()?, max.try_into()?)
+        }
+    ).parse(data)
+}
+
+/// Parses the priority range of an update, where `prio=none` lifts the
+/// cgroup's restriction.
+pub fn parse_priority_update(data: &str) -> nom::IResult<&str, Option<crate::manager::PriorityRange>, ()> {
+    use nom::Parser as _;
+    use nom::branch::*;
+    use nom::bytes::complete::*;
+    use nom::combinator::*;
+
+    alt((
+        value(None, tag("prio=none")),
+        map(parse_priority_range, Some),
+    )).parse(data)
+}
+
+/// Parses the elasticity of a reservation, `max=<runtime us> [weight=<n>]`.
+/// The weight defaults to 1.
+pub fn parse_elasticity(data: &str) -> nom::IResult<&str, crate::manager::Elasticity, ()> {
+    use nom::Parser as _;
+    use nom::bytes::complete::*;
+    use nom::character::complete::*;
+    use nom::combinator::*;
+    use nom::sequence::*;
+
+    map(
+        (
+            preceded(tag("max="), parse_u64),
+            opt(preceded((space1, tag("weight=")), u32)),
+        ),
+        |(max_runtime_us, weight)| crate::manager::Elasticity {
+            max_runtime_us,
+            weight: weight.unwrap_or(1),
+        }
+    ).parse(data)
+}
+
+/// Parses the elasticity of an update, where `max=none` makes the cgroup
+/// rigid again.
+pub fn parse_elasticity_update(data: &str) -> nom::IResult<&str, Option<crate::manager::Elasticity>, ()> {
+    use nom::Parser as _;
+    use nom::branch::*;
+    use nom::bytes::complete::*;
+    use nom::combinator::*;
+
+    alt((
+        value(None, tag("max=none")),
+        map(parse_elasticity, Some),
+    )).parse(data)
+}
+
+/// Parses the CPUs a cgroup is restricted to, `cpus=<cpu list>` in the kernel
+/// list format, e.g. `cpus=2-5,7`.
+pub fn parse_cpus(data: &str) -> nom::IResult<&str, Vec<hcbs_utils::prelude::CpuID>, ()> {
+    use nom::Parser as _;
+    use nom::bytes::complete::*;
+    use nom::character::complete::*;
+    use nom::combinator::*;
+    use nom::sequence::*;
+
+    map_res(
+        preceded(tag("cpus="), recognize((digit1, take_while(|c: char| c.is_ascii_digit() || c == ',' || c == '-')))),
+        crate::tuning::parse_cpu_list
+    ).parse(data)
+}
+
+/// Parses the timing parameters `<period us> [<deadline us> [<wcet us>]]`, or
+/// `clear` to remove them.
+pub fn parse_timing(data: &str) -> nom::IResult<&str, Option<crate::manager::Timing>, ()> {
+    use nom::Parser as _;
+    use nom::branch::*;
+    use nom::bytes::complete::*;
+    use nom::character::complete::*;
+    use nom::combinator::*;
+    use nom::sequence::*;
+
+    alt((
+        value(None, tag("clear")),
+        map_res(
+            (
+                parse_u64,
+                opt(preceded(space1, parse_u64)),
+                opt(preceded(space1, parse_u64)),
+            ),
+            |(period_us, deadline_us, wcet_us)|
+                crate::manager::Timing::new(period_us, deadline_us, wcet_us).map(Some)
+        ),
+    )).parse(data)
+}
+
+/// Parses a task of the schedulability analysis, `<wcet us>,<period us>,<deadline us>,<priority>`.
+pub fn parse_analysis_task(data: &str) -> nom::IResult<&str, crate::manager::AnalysisTask, ()> {
+    use nom::Parser as _;
+    use nom::bytes::complete::*;
+    use nom::combinator::*;
+
+    map_res(
+        (
+            parse_u64,
+            tag(","),
+            parse_u64,
+            tag(","),
+            parse_u64,
+            tag(","),
+            parse_u64,
+        ),
+        |(wcet_us, _, period_us, _, deadline_us, _, priority)| -> anyhow::Result<_> {
+            crate::manager::AnalysisTask::new(wcet_us, period_us, deadline_us, priority.try_into()?)
+        }
+    ).parse(data)
+}
+
 pub fn parse_u64(data: &str) -> nom::IResult<&str, u64, ()> {
     use nom::Parser as _;
     use nom::character::complete::*;
@@ -47,4 +247,314 @@ pub fn parse_u64(data: &str) -> nom::IResult<&str, u64, ()> {
         recognize(digit1),
         |str: &str| str.parse()
     ).parse(data)
-}
\ No newline at end of file
+}
+
+pub fn parse_sched_policy(data: &str) -> nom::IResult<&str, hcbs_utils::prelude::SchedPolicy, ()> {
+    use hcbs_utils::prelude::SchedPolicy;
+    use nom::Parser as _;
+    use nom::branch::*;
+    use nom::bytes::complete::*;
+    use nom::combinator::*;
+    use nom::sequence::*;
+
+    alt((
+        value(
+            SchedPolicy::other(),
+            tag("SCHED_OTHER"),
+        ),
+        map_res(
+            (
+                tag("SCHED_FIFO"),
+                delimited(
+                    tag("("),
+                    parse_u64,
+                    tag(")")
+                )
+            ),
+            |(_, prio)| prio.try_into().map(|prio| SchedPolicy::FIFO(prio))
+        ),
+        map_res(
+            (
+                tag("SCHED_RR"),
+                delimited(
+                    tag("("),
+                    parse_u64,
+                    tag(")")
+                )
+            ),
+            |(_, prio)| prio.try_into().map(|prio| SchedPolicy::RR(prio))
+        )
+    )).parse(data)
+}
+
+/// Parses a scheduling policy, whose priority may be relative to the band of
+/// the cgroup, e.g. `SCHED_FIFO(rel:0)`.
+pub fn parse_policy_request(data: &str) -> nom::IResult<&str, crate::manager::PolicyRequest, ()> {
+    use crate::manager::PolicyRequest;
+    use hcbs_utils::prelude::SchedPolicy;
+    use nom::Parser as _;
+    use nom::branch::*;
+    use nom::bytes::complete::*;
+    use nom::combinator::*;
+    use nom::sequence::*;
+
+    alt((
+        map_res(
+            (
+                tag("SCHED_FIFO"),
+                delimited(
+                    tag("(rel:"),
+                    parse_u64,
+                    tag(")")
+                )
+            ),
+            |(_, offset)| offset.try_into().map(|offset| PolicyRequest::Relative(SchedPolicy::FIFO(offset)))
+        ),
+        map_res(
+            (
+                tag("SCHED_RR"),
+                delimited(
+                    tag("(rel:"),
+                    parse_u64,
+                    tag(")")
+                )
+            ),
+            |(_, offset)| offset.try_into().map(|offset| PolicyRequest::Relative(SchedPolicy::RR(offset)))
+        ),
+        map(
+            parse_sched_policy,
+            PolicyRequest::Absolute
+        ),
+    )).parse(data)
+}
+
+pub fn parse_task_scope(data: &str) -> nom::IResult<&str, crate::manager::TaskScope, ()> {
+    use crate::manager::TaskScope;
+    use nom::Parser as _;
+    use nom::branch::*;
+    use nom::bytes::complete::*;
+    use nom::character::complete::*;
+    use nom::combinator::*;
+    use nom::sequence::*;
+
+    map(
+        opt(preceded(
+            space1,
+            alt((
+                value(TaskScope::Threads, tag("--threads")),
+                value(TaskScope::Tree, tag("--tree")),
+            ))
+        )),
+        |scope| scope.unwrap_or_default()
+    ).parse(data)
+}
+
+pub fn parse_identifier(data: &str) -> nom::IResult<&str, &str, ()> {
+    use nom::Parser as _;
+    use nom::branch::*;
+    use nom::bytes::complete::*;
+    use nom::character::complete::*;
+    use nom::combinator::*;
+    use nom::multi::*;
+
+    recognize((
+        alt((alpha1::<_, ()>, tag("_"))),
+        many0_count(alt((alphanumeric1, tag("_"), tag("-"))))
+    )).parse(data)
+}
+
+pub fn parse_rule(data: &str) -> nom::IResult<&str, crate::manager::Rule, ()> {
+    use nom::Parser as _;
+    use nom::bytes::complete::*;
+    use nom::character::complete::*;
+    use nom::combinator::*;
+    use nom::multi::*;
+    use nom::sequence::*;
+
+    // Trailing input, e.g. a misplaced match, must not be silently dropped.
+    terminated(
+        map(
+            (
+                parse_identifier,
+                many1(preceded(space1, parse_rule_match)),
+                delimited(space1, tag("->"), space1),
+                parse_cgroup_name,
+                space1,
+                parse_sched_policy,
+            ),
+            |(name, matchers, _, cgroup, _, policy)|
+                crate::manager::Rule {
+                    name: name.to_owned(),
+                    matchers,
+                    cgroup: cgroup.to_owned(),
+                    policy,
+                }
+        ),
+        (multispace0, eof),
+    ).parse(data)
+}
+
+pub fn parse_rule_match(data: &str) -> nom::IResult<&str, crate::manager::RuleMatch, ()> {
+    use crate::manager::RuleMatch;
+    use nom::Parser as _;
+    use nom::branch::*;
+    use nom::bytes::complete::*;
+    use nom::combinator::*;
+    use nom::sequence::*;
+
+    let word = || take_till1(|c: char| c.is_whitespace());
+
+    alt((
+        map(
+            preceded(tag("exe="), word()),
+            |exe: &str| RuleMatch::Exe(exe.into())
+        ),
+        map(
+            preceded(tag("comm="), word()),
+            |comm: &str| RuleMatch::Comm(comm.to_owned())
+        ),
+        map_res(
+            preceded(tag("cmdline="), word()),
+            |regex: &str| regex::Regex::new(regex).map(RuleMatch::Cmdline)
+        ),
+        map_res(
+            preceded(tag("uid="), parse_u64),
+            |uid| u32::try_from(uid).map(RuleMatch::Uid)
+        ),
+        map(
+            preceded(tag("parent="), word()),
+            |comm: &str| RuleMatch::Parent(comm.to_owned())
+        ),
+    )).parse(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::manager::{Elasticity, EvictionPolicy, PolicyRequest, PriorityRange, RuleMatch, TaskScope, Timing, TransactionOp};
+    use hcbs_utils::prelude::SchedPolicy;
+
+    #[test]
+    fn priority_range_round_trip() {
+        let range = PriorityRange::new(10, 20).unwrap();
+
+        assert_eq!(parse_priority_range(&format!("prio={range}")), Ok(("", range)));
+        assert!(parse_priority_range("prio=20-10").is_err());
+        assert!(parse_priority_range("prio=0-10").is_err());
+    }
+
+    #[test]
+    fn priority_update_clears_with_none() {
+        assert_eq!(parse_priority_update("prio=none"), Ok(("", None)));
+        assert_eq!(parse_priority_update("prio=40-49"), Ok(("", Some(PriorityRange::new(40, 49).unwrap()))));
+    }
+
+    #[test]
+    fn elasticity_defaults_to_weight_one() {
+        assert_eq!(parse_elasticity("max=5000 weight=3"), Ok(("", Elasticity { max_runtime_us: 5000, weight: 3 })));
+        assert_eq!(parse_elasticity("max=5000"), Ok(("", Elasticity { max_runtime_us: 5000, weight: 1 })));
+        assert_eq!(parse_elasticity_update("max=none"), Ok(("", None)));
+    }
+
+    #[test]
+    fn cpus_use_the_kernel_list_format() {
+        assert_eq!(parse_cpus("cpus=2-5,7"), Ok(("", vec![2, 3, 4, 5, 7])));
+        assert_eq!(parse_cpus("cpus=1 --best-effort"), Ok((" --best-effort", vec![1])));
+        assert!(parse_cpus("cpus=").is_err());
+    }
+
+    #[test]
+    fn transaction_update_accepts_every_option() {
+        let Ok(("", TransactionOp::Update { name, request, priorities, elasticity, cpus, tasks })) =
+            parse_transaction_op("update team/camera 1000 10000 prio=none max=none cpus=1,3")
+            else { panic!("update not parsed") };
+
+        assert_eq!(name, "team/camera");
+        assert_eq!((request.runtime_us, request.period_us), (1000, 10000));
+        assert_eq!(priorities, Some(None));
+        assert_eq!(elasticity, Some(None));
+        assert_eq!(cpus, Some(vec![1, 3]));
+        assert!(tasks.is_none());
+    }
+
+    #[test]
+    fn transaction_create_cannot_clear() {
+        assert!(parse_transaction_op("create camera 1000 10000 prio=none").is_err());
+    }
+
+    #[test]
+    fn transaction_options_must_be_in_order() {
+        assert!(parse_transaction_op("create camera 1000 10000 max=5000 prio=10-20").is_err());
+        assert!(parse_transaction_op("update camera 1000 10000 prio=10-20 typo=1").is_err());
+        assert!(matches!(parse_transaction_op("delete camera\ncreate other 1000 10000"), Ok(("\ncreate other 1000 10000", _))));
+    }
+
+    #[test]
+    fn transaction_delete_eviction() {
+        let Ok(("", TransactionOp::Delete { name, eviction })) = parse_transaction_op("delete camera demote")
+            else { panic!("delete not parsed") };
+
+        assert_eq!(name, "camera");
+        assert_eq!(eviction, Some(EvictionPolicy::Demote));
+        assert_eq!(parse_eviction_policy("leave"), Ok(("", EvictionPolicy::Leave)));
+    }
+
+    #[test]
+    fn timing_round_trip() {
+        let timing = Timing::new(10000, Some(5000), Some(1000)).unwrap();
+
+        assert_eq!(parse_timing("10000 5000 1000"), Ok(("", Some(timing))));
+        assert_eq!(parse_timing("10000"), Ok(("", Some(Timing::new(10000, None, None).unwrap()))));
+        assert_eq!(parse_timing("clear"), Ok(("", None)));
+        assert!(parse_timing("10000 20000").is_err());
+    }
+
+    #[test]
+    fn analysis_task_round_trip() {
+        let task: crate::manager::AnalysisTask = "1000,10000,8000,50".parse().unwrap();
+
+        assert_eq!((task.wcet_us, task.period_us, task.deadline_us, task.priority), (1000, 10000, 8000, 50));
+        assert!(parse_analysis_task("1000,10000,20000,50").is_err());
+    }
+
+    #[test]
+    fn relative_policies() {
+        assert!(matches!(parse_policy_request("SCHED_FIFO(rel:2)"), Ok(("", PolicyRequest::Relative(SchedPolicy::FIFO(2))))));
+        assert!(matches!(parse_policy_request("SCHED_RR(rel:0)"), Ok(("", PolicyRequest::Relative(SchedPolicy::RR(0))))));
+        assert!(matches!(parse_policy_request("SCHED_FIFO(47)"), Ok(("", PolicyRequest::Absolute(SchedPolicy::FIFO(47))))));
+    }
+
+    #[test]
+    fn task_scopes() {
+        assert_eq!(parse_task_scope(""), Ok(("", TaskScope::Process)));
+        assert_eq!(parse_task_scope(" --threads"), Ok(("", TaskScope::Threads)));
+        assert_eq!(parse_task_scope(" --tree"), Ok(("", TaskScope::Tree)));
+        assert_eq!(parse_task_scope(" --forest"), Ok((" --forest", TaskScope::Process)));
+    }
+
+    #[test]
+    fn rule_matches_and_target() {
+        let Ok(("", rule)) = parse_rule("camera exe=/usr/bin/camera uid=1000 -> team/camera SCHED_FIFO(50)\n")
+            else { panic!("rule not parsed") };
+
+        assert_eq!(rule.name, "camera");
+        assert_eq!(rule.cgroup, "team/camera");
+        assert!(matches!(rule.policy, SchedPolicy::FIFO(50)));
+        assert!(matches!(rule.matchers.as_slice(), [RuleMatch::Exe(exe), RuleMatch::Uid(1000)] if exe.as_os_str() == "/usr/bin/camera"));
+    }
+
+    #[test]
+    fn rules_need_a_match_and_nothing_after_the_policy() {
+        assert!(parse_rule("camera -> team/camera SCHED_FIFO(50)").is_err());
+        assert!(parse_rule("camera comm=camera -> team/camera SCHED_FIFO(50) uid=1000").is_err());
+        assert!(parse_rule("camera cmdline=( -> team/camera SCHED_FIFO(50)").is_err());
+    }
+
+    #[test]
+    fn eviction_policies() {
+        assert_eq!(parse_eviction_policy("kill"), Ok(("", EvictionPolicy::Kill)));
+        assert_eq!(parse_eviction_policy("demote"), Ok(("", EvictionPolicy::Demote)));
+        assert_eq!(parse_eviction_policy("original"), Ok(("", EvictionPolicy::Original)));
+        assert!(parse_eviction_policy("keep").is_err());
+    }
+}
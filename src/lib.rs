@@ -1,25 +1,71 @@
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use hcbs_utils::prelude::*;
 
 #[macro_use]
 extern crate log;
 
+mod config;
+pub mod doctor;
 mod filesystem;
 mod manager;
+pub mod ops;
+pub mod simulator;
+mod snapshot;
+mod tuning;
 mod utils;
 
 pub mod prelude {
     pub use super::{
         Controller,
+        config::Config,
+        manager::AdmissionKind,
+        manager::AnalysisTask,
+        manager::Capabilities,
+        manager::CeilingPolicy,
+        manager::EvictionPolicy,
+        manager::ManagerOptions,
+        manager::PriorityAssignment,
+        manager::PriorityRange,
+        manager::Reservation,
+        manager::RestorePolicy,
+        manager::analysis::pareto_reservations,
+        snapshot::SystemSnapshot,
+        tuning::{
+            TuningBackend,
+            TuningBackendKind,
+            TuningOptions,
+            TuningProfile,
+            CpuTuner,
+            RealtimeResetData,
+            parse_cpu_list,
+            tuning_backend,
+            tuning_state_files,
+            setup_realtime_system,
+            reset_realtime_system,
+        },
     };
 }
 
 #[derive(Debug)]
 pub struct Controller {
     mountpoint: &'static str,
+    scanner: Option<ProcessScanner>,
+    state: Arc<Mutex<ControllerState>>,
+}
+
+#[derive(Debug)]
+struct ControllerState {
     manager: manager::HCBSManager,
     process_info: ProcessInfo,
+    config_path: Option<std::path::PathBuf>,
+    /// Names of the rules loaded from the configuration file.
+    config_rules: Vec<String>,
+    /// Tunes the CPUs running the managed reservations, if they are not given
+    /// explicitly. Dropped after the manager, once the reservations are gone.
+    tuner: Option<tuning::CpuTuner>,
 }
 
 #[derive(Debug, Clone)]
@@ -27,34 +73,51 @@ pub struct ProcessStats {
     uid: sysinfo::Uid,
     gid: sysinfo::Gid,
     crtime: std::time::SystemTime,
+    comm: String,
+    exe: Option<std::path::PathBuf>,
+    cmdline: String,
+    parent: Option<sysinfo::Pid>,
 }
 
 impl Controller {
     const DEFAULT_MOUNT_POINT: &'static str = "/mnt/hcbs-manager";
 
-    pub fn new(keep_on_exit: bool) -> Self {
+    /// The rules in the given configuration file are loaded when mounting,
+    /// and reloaded on SIGHUP. The tuner, if any, follows the CPUs of the
+    /// managed cgroups.
+    pub fn new(options: manager::ManagerOptions, config_path: Option<std::path::PathBuf>, tuner: Option<tuning::CpuTuner>) -> Self {
         Self {
             mountpoint: Self::DEFAULT_MOUNT_POINT,
-            manager: manager::HCBSManager::new(keep_on_exit),
-            process_info: ProcessInfo::new(),
+            scanner: None,
+            state: Arc::new(Mutex::new(ControllerState {
+                manager: manager::HCBSManager::new(options),
+                process_info: ProcessInfo::new(),
+                config_path,
+                config_rules: Vec::with_capacity(0),
+                tuner,
+            })),
         }
     }
 
-    pub fn mount(self) -> anyhow::Result<()> {
+    /// Mounts the filesystem and serves requests until it is unmounted, either
+    /// externally or on SIGTERM/SIGINT. The managed cgroups and processes are
+    /// torn down before returning. Returns the signal which shut down the
+    /// filesystem, if any.
+    pub fn mount(mut self) -> anyhow::Result<Option<i32>> {
         let mountpoint = self.mountpoint;
 
-        ctrlc::set_handler(move || {
-            std::process::Command::new("umount")
-                .arg(mountpoint)
-                .output()
-                .unwrap();
-        })?;
+        self.state().reload_config()?;
+        self.state().create_config_cgroups()?;
+        self.state().retune();
 
         let _mountdir = utils::TempDir::new(mountpoint)?;
 
-        fuser::mount2(
+        self.scanner = Some(ProcessScanner::spawn(self.state.clone()));
+
+        let state = self.state.clone();
+        let mut session = fuser::Session::new(
             self,
-            mountpoint,
+            std::path::Path::new(mountpoint),
             &[
                 fuser::MountOption::AllowOther,
                 fuser::MountOption::AutoUnmount,
@@ -65,13 +128,240 @@ impl Controller {
             ]
         )?;
 
-        Ok(())
+        let mut signals = SignalHandler::spawn(session.unmount_callable(), state)?;
+
+        let result = session.run();
+
+        // Stop handling signals before the teardown, so that the manager is
+        // dropped together with the session.
+        let signal = signals.stop();
+        drop(session);
+
+        result?;
+
+        if let Some(signal) = signal {
+            info!("Shut down on signal {signal}");
+        }
+
+        Ok(signal)
     }
 
+    fn state(&self) -> MutexGuard<'_, ControllerState> {
+        self.state.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl ControllerState {
     pub fn update(&mut self) {
         let dead = self.process_info.update_active_processes();
 
         self.manager.update_managed_processes(dead);
+
+        let active_procs = &self.process_info.active_procs;
+        let spawned =
+            self.process_info.spawned_procs.iter()
+            .filter_map(|pid| {
+                let stats = active_procs.get(pid)?;
+                let parent = stats.parent.and_then(|parent| active_procs.get(&parent));
+
+                Some((pid.as_u32(), stats, parent))
+            });
+
+        self.manager.apply_rules(spawned);
+    }
+
+    /// Tunes the CPUs which run the managed reservations, and restores the
+    /// ones which no longer do.
+    pub fn retune(&mut self) {
+        let Some(tuner) = &mut self.tuner
+            else { return; };
+
+        if let Err(err) = tuner.tune(&self.manager.reservation_cpus()) {
+            error!("Couldn't tune the CPUs running the reservations: {err}");
+        }
+    }
+
+    /// Creates the cgroups listed in the configuration file, owned by root,
+    /// together with their task sets.
+    pub fn create_config_cgroups(&mut self) -> anyhow::Result<()> {
+        let Some(path) = &self.config_path
+            else { return Ok(()); };
+
+        let config = config::Config::load(path)?;
+
+        for cgroup in config.cgroups.iter() {
+            let tasks = cgroup.tasks()?;
+
+            self.manager.create_cgroup(&cgroup.name, cgroup.reservation(), None, None, None, &manager::Requester::manager())?;
+            self.manager.set_cgroup_tasks(&cgroup.name, tasks, &manager::Requester::manager())?;
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the rules, quotas, authorization policy and modes loaded from
+    /// the configuration file with the ones currently in the file. Rules added
+    /// through the filesystem are kept. Nothing changes if the file is not
+    /// valid.
+    pub fn reload_config(&mut self) -> anyhow::Result<()> {
+        let Some(path) = &self.config_path
+            else { return Ok(()); };
+
+        let config = config::Config::load(path)?;
+
+        let rules: Vec<_> =
+            config.rules.iter()
+            .map(|rule| {
+                filesystem::parser::parse_rule(rule)
+                    .map(|(_, rule)| rule)
+                    .map_err(|_| anyhow::format_err!("Invalid rule in configuration file: \"{rule}\""))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        let quotas =
+            config.quotas.iter()
+            .map(|quota| quota.quota())
+            .collect::<anyhow::Result<_>>()?;
+
+        let auth_policy =
+            config.policy.as_ref()
+            .map(manager::AuthPolicy::load)
+            .transpose()?;
+
+        let modes =
+            config.modes.iter()
+            .map(|mode| mode.mode())
+            .collect::<anyhow::Result<_>>()?;
+
+        self.manager.set_quotas(quotas);
+        self.manager.set_auth_policy(auth_policy);
+        self.manager.set_modes(modes);
+
+        for name in self.config_rules.drain(..) {
+            self.manager.remove_rule(&name, &manager::Requester::manager()).ok();
+        }
+
+        self.config_rules = rules.iter().map(|rule| rule.name.clone()).collect();
+        for rule in rules {
+            self.manager.add_rule(rule, &manager::Requester::manager())?;
+        }
+
+        info!("Loaded {} rules from {}", self.config_rules.len(), path.display());
+
+        Ok(())
+    }
+}
+
+/// Periodically refreshes the active processes, so that rules are applied to
+/// newly spawned processes even when nobody is accessing the filesystem, and
+/// the tuned CPUs.
+#[derive(Debug)]
+struct ProcessScanner {
+    stop: Arc<AtomicBool>,
+    handle: Option<std::thread::JoinHandle<()>>,
+}
+
+impl ProcessScanner {
+    fn spawn(state: Arc<Mutex<ControllerState>>) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let handle = {
+            let stop = stop.clone();
+
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    std::thread::sleep(ProcessInfo::UPDATE_DELTA);
+
+                    let mut state =
+                        state.lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+                    state.update();
+                    // Cpusets can also be changed outside of the manager.
+                    state.retune();
+                }
+            })
+        };
+
+        Self { stop, handle: Some(handle) }
+    }
+}
+
+impl Drop for ProcessScanner {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        if let Some(handle) = self.handle.take() {
+            if handle.join().is_err() {
+                error!("Process scanner thread panicked");
+            }
+        }
+    }
+}
+
+/// Handles signals while the filesystem is mounted. SIGTERM and SIGINT
+/// unmount the filesystem, so that the controller returns and tears down the
+/// managed cgroups and processes in order. SIGHUP reloads the configuration.
+struct SignalHandler {
+    handle: signal_hook::iterator::Handle,
+    thread: Option<std::thread::JoinHandle<Option<i32>>>,
+}
+
+impl SignalHandler {
+    fn spawn(mut unmounter: fuser::SessionUnmounter, state: Arc<Mutex<ControllerState>>) -> anyhow::Result<Self> {
+        use signal_hook::consts::*;
+
+        let mut signals = signal_hook::iterator::Signals::new([SIGTERM, SIGINT, SIGHUP])?;
+        let handle = signals.handle();
+
+        let thread = std::thread::spawn(move || {
+            for signal in signals.forever() {
+                if signal == SIGHUP {
+                    let result =
+                        state.lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner())
+                        .reload_config();
+
+                    if let Err(err) = result {
+                        error!("Couldn't reload the configuration: {err}");
+                    }
+
+                    continue;
+                }
+
+                info!("Received signal {signal}, shutting down");
+                match unmounter.unmount() {
+                    Ok(()) => return Some(signal),
+                    Err(err) => error!("Couldn't unmount the filesystem: {err}"),
+                }
+            }
+
+            None
+        });
+
+        Ok(Self { handle, thread: Some(thread) })
+    }
+
+    /// Stops handling signals. Returns the signal which shut down the
+    /// filesystem, if any.
+    fn stop(&mut self) -> Option<i32> {
+        self.handle.close();
+
+        let thread = self.thread.take()?;
+        match thread.join() {
+            Ok(signal) => signal,
+            Err(_) => {
+                error!("Signal handler thread panicked");
+                None
+            },
+        }
+    }
+}
+
+impl Drop for SignalHandler {
+    fn drop(&mut self) {
+        self.stop();
     }
 }
 
@@ -79,7 +369,9 @@ impl Controller {
 struct ProcessInfo {
     sysinfo: sysinfo::System,
     active_procs: HashMap<sysinfo::Pid, ProcessStats>,
+    spawned_procs: Vec<sysinfo::Pid>,
     last_update: std::time::Instant,
+    first_update: bool,
 }
 
 impl ProcessInfo {
@@ -89,7 +381,9 @@ impl ProcessInfo {
         Self {
             sysinfo: sysinfo::System::new(),
             active_procs: HashMap::with_capacity(0),
+            spawned_procs: Vec::with_capacity(0),
             last_update: std::time::Instant::now() - Self::UPDATE_DELTA * 2,
+            first_update: true,
         }
     }
 
@@ -98,6 +392,7 @@ impl ProcessInfo {
 
         let now = std::time::Instant::now();
         if now - self.last_update <= Self::UPDATE_DELTA {
+            self.spawned_procs.clear();
             return Box::new(std::iter::empty());
         }
 
@@ -105,22 +400,45 @@ impl ProcessInfo {
             ProcessesToUpdate::All,
             true,
             ProcessRefreshKind::nothing()
-                .with_user(UpdateKind::Always));
+                .with_user(UpdateKind::Always)
+                .with_exe(UpdateKind::OnlyIfNotSet)
+                .with_cmd(UpdateKind::OnlyIfNotSet));
 
         let (alive, dead): (Vec<_>, Vec<_>) =
             self.sysinfo.processes().iter()
                 .partition(|(_, p)| p.exists());
 
-        self.active_procs =
+        let active_procs: HashMap<_, _> =
                 alive.into_iter()
                 .map(|(_, p)|
                 (p.pid(), ProcessStats {
                     uid: p.user_id().unwrap().clone(),
                     gid: p.group_id().unwrap().clone(),
                     crtime: std::time::UNIX_EPOCH + std::time::Duration::from_secs(p.start_time()),
+                    comm: p.name().to_string_lossy().into_owned(),
+                    exe: p.exe().map(|exe| exe.to_owned()),
+                    cmdline: p.cmd().iter()
+                        .map(|arg| arg.to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                    parent: p.parent(),
                 }))
                 .collect();
 
+        // Processes already alive when the manager starts are not considered
+        // as spawned, so rules only apply to processes started afterwards.
+        self.spawned_procs =
+            if self.first_update {
+                Vec::with_capacity(0)
+            } else {
+                active_procs.keys()
+                    .filter(|pid| !self.active_procs.contains_key(pid))
+                    .copied()
+                    .collect()
+            };
+
+        self.active_procs = active_procs;
+        self.first_update = false;
         self.last_update = std::time::Instant::now();
 
         Box::new(dead.into_iter().map(|(pid, _)| pid.as_u32()))
@@ -1,20 +1,114 @@
 use hcbs_manager::prelude::*;
 use hcbs_utils::prelude::*;
+use hcbs_manager::ops::{
+    assign_pid_to_cgroup,
+    mount_cgroup_fs,
+    set_cgroup_runtime_us,
+    set_sched_policy,
+};
 
 #[derive(Debug, clap::Parser)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Configuration file
+    ///
+    /// TOML file with the manager's configuration. Command line arguments
+    /// take precedence over the configuration file.
+    #[arg(short='c', long="config", global=true)]
+    config: Option<std::path::PathBuf>,
+
     /// Max bandwidth of the Cgroup hierarchy
     #[arg(short='b', long="bandwidth", default_value="0.9")]
     runtime_bw: f64,
 
     /// Reset changes on exit
     ///
-    /// This resets the cgroup hierarchy allocated bandwidth and changes all
-    /// touched processes back to SCHED_OTHER, moving them to the cgroup they
-    /// were into before being moved around by commands.
-    #[arg(short='e')]
+    /// This resets the cgroup hierarchy allocated bandwidth and restores all
+    /// touched processes to the cgroup, scheduling policy, nice value and CPU
+    /// affinity they had before being moved around by commands. Processes are
+    /// also restored when their managed cgroup is deleted.
+    #[arg(short='e', long="reset-on-exit")]
     reset_on_exit: bool,
 
+    /// Eviction policy on exit
+    ///
+    /// What happens to the processes still running in managed cgroups when the
+    /// manager exits: "kill" them, "demote" them to SCHED_OTHER in the root
+    /// cgroup, restore them to their "original" cgroup, or "leave" the cgroups
+    /// in place.
+    #[arg(long="eviction", value_enum, default_value="kill")]
+    eviction_policy: EvictionPolicy,
+
+    /// Priority ceiling policy
+    ///
+    /// What happens to SCHED_FIFO/SCHED_RR requests with a priority outside
+    /// the priority range of the target cgroup: "reject" them, or "clamp" the
+    /// priority into the range.
+    #[arg(long="ceiling-policy", value_enum, default_value="reject")]
+    ceiling_policy: CeilingPolicy,
+
+    /// Automatic priority assignment
+    ///
+    /// Order of the SCHED_FIFO priorities assigned to the processes with
+    /// timing parameters in the same managed cgroup: "rate-monotonic" (shorter
+    /// periods first) or "deadline-monotonic" (shorter deadlines first).
+    #[arg(long="priority-assignment", value_enum, default_value="rate-monotonic")]
+    priority_assignment: PriorityAssignment,
+
+    /// Admission policy
+    ///
+    /// Test applied to cgroup creations and updates: "utilisation" (the total
+    /// bandwidth must fit under the cap), "hierarchical" (nested cgroups must
+    /// fit in the reservation of their managed parent, the top-level ones
+    /// under the cap), "per-cpu" (the cgroups running on each CPU must fit
+    /// under the cap), "analysis" (as "utilisation", and the task set of the
+    /// cgroup must be schedulable in its reservation) or "accept-all" (for
+    /// experiments).
+    #[arg(long="admission", value_enum, default_value="utilisation")]
+    admission: AdmissionKind,
+
+    /// System tuning backend
+    ///
+    /// Backend used to fix the CPU frequencies for real-time workloads.
+    /// Available values: "auto", "intel-pstate", "amd-pstate", "cpufreq",
+    /// "none". With "auto", the first available backend is used, falling back
+    /// to "none" which leaves the system untouched.
+    #[arg(long="tuning-backend", value_enum, default_value="auto")]
+    tuning_backend: TuningBackendKind,
+
+    /// System tuning profile
+    ///
+    /// Available values: "none", "frequency" (only fix the CPU frequencies),
+    /// "full" (also disable hyperthreading and idle states). Defaults to the
+    /// configuration file's profile, or "full".
+    #[arg(long="tuning-profile", value_enum)]
+    tuning_profile: Option<TuningProfile>,
+
+    /// CPUs to tune
+    ///
+    /// CPUs running the managed reservations, in the kernel list format (e.g.
+    /// "2-5,7"). Only these CPUs have their frequency and idle states tuned.
+    /// Defaults to the CPUs of the managed cgroups' cpusets, tuned as cgroups
+    /// are created and destroyed.
+    #[arg(long="tuning-cpus")]
+    tuning_cpus: Option<String>,
+
+    /// Disable system tuning
+    ///
+    /// Same as "--tuning-profile none".
+    #[arg(long="no-system-tuning", conflicts_with="tuning_profile")]
+    no_system_tuning: bool,
+
+    /// Print changes instead of applying them
+    ///
+    /// Every change to cgroups, processes and system settings is printed
+    /// instead of being applied. The system is still read and requests are
+    /// still checked for admission.
+    #[arg(long="dry-run", global=true)]
+    dry_run: bool,
+
     /// Log level
     ///
     /// Available values: "off", "error", "warn", "info", "debug", "trace"
@@ -22,6 +116,51 @@ struct Args {
     log_level: log::LevelFilter,
 }
 
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Restore the system state saved by a run which did not exit cleanly
+    ///
+    /// The manager saves the original system settings before changing them.
+    /// If it crashes or is killed, this command reapplies the saved settings.
+    Restore,
+
+    /// Check whether the system is ready to run the manager
+    ///
+    /// Each check reports PASS, WARN or FAIL, with a hint on how to fix
+    /// the issue. Exits with an error if any check fails.
+    Doctor,
+
+    /// Compute reservations which make a task set schedulable
+    ///
+    /// Prints the Pareto-optimal runtime/period pairs, by increasing
+    /// bandwidth, within the periods supported by the running kernel. Tasks
+    /// are given as "<wcet us>,<period us>,<deadline us>,<priority>".
+    Plan {
+        #[arg(required=true)]
+        tasks: Vec<AnalysisTask>,
+    },
+
+    /// Simulate the cgroups of the configuration file
+    ///
+    /// Runs the task sets of the configuration file's cgroups inside their
+    /// reservations, and reports deadline misses, response times and the
+    /// utilisation of each cgroup. Exits with an error if any deadline is
+    /// missed.
+    Simulate {
+        /// Number of CPUs, each running a server of every cgroup
+        #[arg(long="cpus", default_value="1")]
+        cpus: usize,
+
+        /// Simulated time, in milliseconds
+        #[arg(long="duration-ms", default_value="1000")]
+        duration_ms: u64,
+
+        /// Write the execution trace to the given CSV file
+        #[arg(long="trace")]
+        trace: Option<std::path::PathBuf>,
+    },
+}
+
 fn main() -> anyhow::Result<()> {
     let args: Args = clap::Parser::parse();
 
@@ -30,130 +169,269 @@ fn main() -> anyhow::Result<()> {
         .filter_level(args.log_level)
         .init();
 
+    hcbs_manager::ops::set_dry_run(args.dry_run);
+
+    match &args.command {
+        Some(Command::Restore) => return restore_system(),
+        Some(Command::Doctor) => return doctor(),
+        Some(Command::Plan { tasks }) => return plan(tasks),
+        Some(Command::Simulate { cpus, duration_ms, trace }) =>
+            return simulate(&args, *cpus, *duration_ms, trace.as_deref()),
+        None => (),
+    }
+
+    let config = match &args.config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+
+    // Look for the state of a previous run which did not exit cleanly
+    check_stale_snapshot()?;
+
+    // Mount Cgroup filesystem and CPU controller
+    mount_cgroup_fs()?;
+
+    // Save the system state before changing it
+    let tuning_options = tuning_options(&args, &config)?;
+    let backend = tuning_backend(args.tuning_backend)?;
+    let root_runtime_us =
+        if args.reset_on_exit { Some(get_cgroup_runtime_us(ROOT_CGROUP)?) }
+        else { None };
+    SystemSnapshot::capture(
+        tuning_state_files(backend.as_ref(), &tuning_options)?,
+        root_runtime_us,
+    )?.save()?;
+
     // Set manager to run on real-time scheduling policy
     assign_pid_to_cgroup(ROOT_CGROUP, 0)?;
-    set_sched_policy(0, SchedPolicy::FIFO(99))?;
+    set_sched_policy(0, SchedPolicy::FIFO(PriorityRange::MANAGER_PRIORITY))?;
 
     // Setup System for Real-Time workloads
-    setup_reset_helper(
-        setup_realtime_system,
-        reset_realtime_system,
+    let result = setup_reset_helper(
+        || setup_realtime_system(backend.as_ref(), &tuning_options),
+        |data| reset_realtime_system(backend.as_ref(), data),
         // Setup HCBS Hierarchy
         || setup_reset_helper(
             || setup_hcbs(&args),
             |data| reset_hcbs(&args, data),
             // Start HCBS Manager
             || {
+                let restore_policy =
+                    if args.reset_on_exit { RestorePolicy::Restore }
+                    else { RestorePolicy::Keep };
+
                 Controller::new(
-                    args.reset_on_exit
+                    ManagerOptions {
+                        restore_policy,
+                        eviction_policy: args.eviction_policy,
+                        ceiling_policy: args.ceiling_policy,
+                        priority_assignment: args.priority_assignment,
+                        admission: args.admission,
+                    },
+                    args.config.clone(),
+                    tuning_options.cpus.is_none()
+                        .then(|| CpuTuner::new(backend.clone(), tuning_options.clone())),
                 ).mount()
             }
         )
-    )
-}
+    );
 
-struct HCBSResetData {
-    old_runtime_us: u64,
+    // The snapshot is kept if anything failed, as the system may not have
+    // been fully reset.
+    match &result {
+        Ok(_) => SystemSnapshot::remove()?,
+        Err(_) => log::error!("The system may not have been reset, run \"hcbs-manager restore\" to restore it"),
+    }
+
+    // Like the default signal handlers, report the shutdown signal in the
+    // exit status, after a clean teardown.
+    if let Some(signal) = result? {
+        std::process::exit(128 + signal);
+    }
+
+    Ok(())
 }
 
-fn setup_hcbs(args: &Args) -> anyhow::Result<HCBSResetData> {
-    // Mount Cgroup filesystem and CPU controller
-    mount_cgroup_fs()?;
+fn check_stale_snapshot() -> anyhow::Result<()> {
+    let Some(snapshot) = SystemSnapshot::load()?
+        else { return Ok(()); };
 
-    // Reserve bandwidth for the CGroup hierarchy
-    let period_us = get_cgroup_period_us(ROOT_CGROUP)?;
-    let old_runtime_us = get_cgroup_runtime_us(ROOT_CGROUP)?;
-    let runtime_us = (args.runtime_bw * period_us as f64).floor() as u64;
-    set_cgroup_runtime_us(ROOT_CGROUP, runtime_us)?;
+    if snapshot.is_from_current_boot()? {
+        anyhow::bail!("Found the system state saved by a previous run which did not exit cleanly ({}). \
+            Run \"hcbs-manager restore\" to restore it first.", SystemSnapshot::PATH);
+    }
 
-    Ok(HCBSResetData {
-        old_runtime_us,
-    })
+    // Rebooting resets all the saved settings.
+    log::warn!("Discarding the system state saved before the last reboot");
+    SystemSnapshot::remove()
 }
 
-fn reset_hcbs(args: &Args, data: HCBSResetData) -> anyhow::Result<()> {
-    if !args.reset_on_exit {
-        return Ok(());
+fn doctor() -> anyhow::Result<()> {
+    use hcbs_manager::doctor::*;
+
+    let results = run_checks();
+
+    for result in results.iter() {
+        println!("[{}] {}: {}", result.status, result.name, result.message);
+        if let Some(hint) = result.hint {
+            println!("       hint: {hint}");
+        }
     }
 
-    // Disable Cgroup hierarchy
-    set_cgroup_runtime_us(ROOT_CGROUP, data.old_runtime_us)?;
+    let failed = results.iter().filter(|result| result.status == CheckStatus::Fail).count();
+    if failed > 0 {
+        anyhow::bail!("{failed} checks failed");
+    }
 
     Ok(())
 }
 
-struct RealtimeResetData {
-    hyperthreading_enabled: bool,
-    intel_pstate: intel::PState,
-    cpu_freq_governor_data: Vec<(CpuID, CpuFrequencyGovernorData)>,
-    cpu_idle_states: Vec<(CpuID, CpuIdleStates)>,
+fn plan(tasks: &[AnalysisTask]) -> anyhow::Result<()> {
+    let capabilities = Capabilities::probe();
+    let reservations = pareto_reservations(tasks, capabilities.min_period_us, capabilities.max_period_us);
+
+    if reservations.is_empty() {
+        anyhow::bail!("No reservation makes the task set schedulable");
+    }
+
+    println!("runtime_us period_us bandwidth");
+    for reservation in reservations {
+        println!("{} {} {:.3}", reservation.runtime_us, reservation.period_us, reservation.bandwidth());
+    }
+
+    Ok(())
 }
 
-fn setup_realtime_system() -> anyhow::Result<RealtimeResetData> {
-    // Hyperthreading and Frequency Governors
-    if !intel::has_intel_pstate()? {
-        anyhow::bail!("Only Intel CPUs supported")
+fn simulate(args: &Args, cpus: usize, duration_ms: u64, trace: Option<&std::path::Path>) -> anyhow::Result<()> {
+    use hcbs_manager::simulator::*;
+
+    let Some(path) = &args.config
+        else { anyhow::bail!("The cgroups to simulate are read from the configuration file, given with -c"); };
+
+    let cgroups =
+        Config::load(path)?.cgroups.iter()
+        .map(|cgroup| Ok(SimCgroup {
+            name: cgroup.name.clone(),
+            reservation: cgroup.reservation(),
+            tasks: cgroup.tasks()?,
+        }))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let options = SimulationOptions {
+        cpus,
+        duration_us: duration_ms * 1000,
+        trace: trace.is_some(),
+    };
+
+    let report = hcbs_manager::simulator::simulate(&cgroups, &options)?;
+
+    for cgroup in report.cgroups.iter() {
+        println!("cgroup {} reservation {} {} bandwidth {:.3} utilisation {:.3}",
+            cgroup.name, cgroup.reservation.runtime_us, cgroup.reservation.period_us,
+            cgroup.reservation.bandwidth(), cgroup.utilisation(report.duration_us));
     }
 
-    // Get current system state
-    let cpus = CpuSet::all()?;
-    let hyperthreading_enabled = hyperthreading_enabled()?;
-    let intel_pstate = intel::get_pstate()?;
-    let cpu_freq_governor_data =
-        cpus.iter()
-        .map(|&cpu| Ok((cpu, get_cpu_frequency_governor(cpu)?)) )
-        .collect::<anyhow::Result<_>>()?;
-    let cpu_idle_states =
-        cpus.iter()
-        .map(|&cpu| Ok((cpu, get_cpu_idle_state(cpu)?)) )
-        .collect::<anyhow::Result<_>>()?;
-
-    // Set Real-Time system state
-    if hyperthreading_enabled {
-        disable_hyperthreading()?;
+    let show = |value: Option<u64>| value.map_or_else(|| "-".to_owned(), |value| value.to_string());
+    for task in report.tasks.iter() {
+        println!("task {}/{} jobs {} misses {} response_us min {} mean {} p50 {} p99 {} max {}",
+            report.cgroups[task.cgroup].name, task.task, task.jobs, task.misses,
+            show(task.percentile(0.0)),
+            task.mean().map_or_else(|| "-".to_owned(), |mean| format!("{mean:.1}")),
+            show(task.percentile(0.5)),
+            show(task.percentile(0.99)),
+            show(task.percentile(1.0)));
     }
-        // refresh CPUs as disabling hyperthreading switches off some logical cores.
-    let cpus = CpuSet::all()?;
-    intel::set_pstate(intel::PState::fix_performance())?;
-    for &cpu in cpus.iter() {
-        let freqs = get_cpu_frequency(cpu)?;
 
-        set_cpu_frequency_governor(cpu, CpuFrequencyGovernorData::fixed_frequency(freqs.max_frequency_mhz))?;
+    if let Some(path) = trace {
+        let file = std::fs::File::create(path)
+            .map_err(|err| anyhow::format_err!("Cannot create trace file {}: {err}", path.display()))?;
+
+        report.write_trace(std::io::BufWriter::new(file))?;
     }
-    for &cpu in cpus.iter() {
-        let cpu = cpu as u32;
-        set_cpu_idle_state(cpu, CpuIdleStates::disabled_for_cpu(cpu)?)?;
+
+    let misses = report.misses();
+    if misses > 0 {
+        anyhow::bail!("{misses} deadlines missed");
     }
 
-    Ok(RealtimeResetData {
-        hyperthreading_enabled,
-        intel_pstate,
-        cpu_freq_governor_data,
-        cpu_idle_states
-    })
+    Ok(())
 }
 
-fn reset_realtime_system(data: RealtimeResetData) -> anyhow::Result<()> {
-    // Reset Real-Time system state
-    if data.hyperthreading_enabled { enable_hyperthreading()? }
-    intel::set_pstate(data.intel_pstate)?;
-    for (cpu, data) in data.cpu_freq_governor_data.into_iter() {
-        set_cpu_frequency_governor(cpu, data)?;
+fn restore_system() -> anyhow::Result<()> {
+    let Some(snapshot) = SystemSnapshot::load()?
+        else { anyhow::bail!("No saved system state found at {}", SystemSnapshot::PATH); };
+
+    if !snapshot.is_from_current_boot()? {
+        log::warn!("The saved system state is from before the last reboot, discarding it");
+        return SystemSnapshot::remove();
     }
-    for (cpu, data) in data.cpu_idle_states.into_iter() {
-        set_cpu_idle_state(cpu, data)?;
+
+    snapshot.restore()?;
+    SystemSnapshot::remove()
+}
+
+fn tuning_options(args: &Args, config: &Config) -> anyhow::Result<TuningOptions> {
+    let profile =
+        if args.no_system_tuning { TuningProfile::None }
+        else {
+            args.tuning_profile
+                .or(config.tuning.profile)
+                .unwrap_or(TuningProfile::Full)
+        };
+
+    let cpus =
+        args.tuning_cpus.as_ref()
+        .or(config.tuning.cpus.as_ref())
+        .map(|cpus| parse_cpu_list(cpus))
+        .transpose()?;
+
+    let mut options = profile.options(cpus);
+
+    // The configuration file can toggle single steps, unless the tuning is
+    // disabled from the command line.
+    if !args.no_system_tuning && args.tuning_profile.is_none() {
+        options.hyperthreading = config.tuning.hyperthreading.unwrap_or(options.hyperthreading);
+        options.frequency = config.tuning.frequency.unwrap_or(options.frequency);
+        options.idle_states = config.tuning.idle_states.unwrap_or(options.idle_states);
     }
 
+    Ok(options)
+}
+
+struct HCBSResetData {
+    old_runtime_us: u64,
+}
+
+fn setup_hcbs(args: &Args) -> anyhow::Result<HCBSResetData> {
+    // Reserve bandwidth for the CGroup hierarchy
+    let period_us = get_cgroup_period_us(ROOT_CGROUP)?;
+    let old_runtime_us = get_cgroup_runtime_us(ROOT_CGROUP)?;
+    let runtime_us = (args.runtime_bw * period_us as f64).floor() as u64;
+    set_cgroup_runtime_us(ROOT_CGROUP, runtime_us)?;
+
+    Ok(HCBSResetData {
+        old_runtime_us,
+    })
+}
+
+fn reset_hcbs(args: &Args, data: HCBSResetData) -> anyhow::Result<()> {
+    if !args.reset_on_exit {
+        return Ok(());
+    }
+
+    // Disable Cgroup hierarchy
+    set_cgroup_runtime_us(ROOT_CGROUP, data.old_runtime_us)?;
+
     Ok(())
 }
 
-fn setup_reset_helper<F, D, FSetup, FReset>(
+fn setup_reset_helper<F, T, D, FSetup, FReset>(
     setup: FSetup,
     reset: FReset,
     f: F,
-) -> anyhow::Result<()>
+) -> anyhow::Result<T>
     where
-        F: FnOnce() -> anyhow::Result<()>,
+        F: FnOnce() -> anyhow::Result<T>,
         FSetup: FnOnce() -> anyhow::Result<D>,
         FReset: FnOnce(D) -> anyhow::Result<()>,
 {
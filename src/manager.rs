@@ -1,61 +1,685 @@
+use std::collections::HashMap;
+
 use hcbs_utils::prelude::*;
 
+pub mod admission;
+pub mod analysis;
+pub mod auth;
+pub mod capabilities;
 pub mod cgroup;
 pub mod proc;
+pub mod quota;
+pub mod rules;
+pub mod timing;
+pub mod transaction;
 
 use cgroup::*;
 use proc::*;
+use rules::*;
+
+use auth::Operation;
+use transaction::Applied;
 
-pub use cgroup::Reservation;
+pub use admission::{AdmissionKind, AdmissionPolicy, Verdict};
+pub use analysis::AnalysisTask;
+pub use auth::{AuthPolicy, Requester};
+pub use capabilities::Capabilities;
+pub use cgroup::{CgroupData, Elasticity, EvictionPolicy, PolicyRequest, PriorityRange, Reservation};
+pub use proc::{CeilingPolicy, RestorePolicy, TaskScope};
+pub use quota::{Quota, QuotaSubject, QuotaUsage};
+pub use rules::{Rule, RuleMatch};
+pub use timing::{PriorityAssignment, Timing};
+pub use transaction::{Mode, ModeCgroup, TransactionOp};
 
 #[derive(Debug)]
 pub struct HCBSManager {
     cgroups: CgroupManager,
     procs: ProcManager,
+    rules: RulesManager,
+    eviction_policy: EvictionPolicy,
+    capabilities: Capabilities,
+    auth_policy: Option<AuthPolicy>,
+    /// Last reservations suggested for each cgroup name.
+    sizings: HashMap<String, Vec<Reservation>>,
+    modes: Vec<Mode>,
+    active_mode: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ManagerOptions {
+    /// What happens to managed processes on exit and on cgroup deletion.
+    pub restore_policy: RestorePolicy,
+    /// What happens to the processes of managed cgroups on exit.
+    pub eviction_policy: EvictionPolicy,
+    /// What happens to priorities outside of a cgroup's priority range.
+    pub ceiling_policy: CeilingPolicy,
+    /// Order of the priorities assigned to processes with timing parameters.
+    pub priority_assignment: PriorityAssignment,
+    /// Admission test of the cgroup reservations.
+    pub admission: AdmissionKind,
 }
 
 impl HCBSManager {
-    pub fn new(keep_on_exit: bool) -> Self {
+    pub fn new(options: ManagerOptions) -> Self {
         Self {
-            cgroups: CgroupManager::new(),
-            procs: ProcManager::new(keep_on_exit),
+            cgroups: CgroupManager::new(options.admission.policy()),
+            procs: ProcManager::new(options.restore_policy, options.ceiling_policy, options.priority_assignment),
+            rules: RulesManager::new(),
+            eviction_policy: options.eviction_policy,
+            capabilities: Capabilities::probe(),
+            auth_policy: None,
+            sizings: HashMap::new(),
+            modes: Vec::with_capacity(0),
+            active_mode: None,
         }
     }
 
+    pub fn capabilities(&self) -> &Capabilities {
+        &self.capabilities
+    }
+
     pub fn update_managed_processes<I>(&mut self, dead_procs: I)
         where I: Iterator<Item = Pid>
     {
-        self.procs.update_managed_processes(dead_procs);
+        self.procs.update_managed_processes(&self.cgroups, dead_procs);
+    }
+
+    /// Places newly spawned processes according to the first matching rule.
+    /// Processes are placed through the same checks of explicit requests,
+    /// with the manager's privileges: only root can add rules.
+    pub fn apply_rules<'a, I>(&mut self, spawned_procs: I)
+        where I: Iterator<Item = (Pid, &'a crate::ProcessStats, Option<&'a crate::ProcessStats>)>
+    {
+        for (pid, stats, parent) in spawned_procs {
+            let Some(rule) = self.rules.matching_rule(stats, parent).cloned()
+                else { continue; };
+
+            match self.place_process(pid, &rule.cgroup, PolicyRequest::Absolute(rule.policy.clone()), &Requester::manager()) {
+                Ok(()) => info!("Rule \"{}\" placed PID {pid} into cgroup \"{}\"", rule.name, rule.cgroup),
+                Err(err) => warn!("Rule \"{}\" couldn't place PID {pid}: {err}", rule.name),
+            }
+        }
+    }
+
+    pub fn add_rule(&mut self, rule: Rule, requester: &Requester) -> anyhow::Result<()> {
+        check_rules_access(requester)?;
+
+        self.rules.add_rule(rule);
+
+        Ok(())
+    }
+
+    pub fn remove_rule(&mut self, name: &str, requester: &Requester) -> anyhow::Result<()> {
+        check_rules_access(requester)?;
+
+        self.rules.remove_rule(name)
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &Rule> {
+        self.rules.rules()
+    }
+
+    /// Creates a managed cgroup owned by the requester. Without a priority
+    /// range, its processes can use any priority below the manager's. Elastic
+    /// cgroups are only guaranteed the requested runtime. Without CPUs, the
+    /// cgroup runs on every CPU.
+    pub fn create_cgroup(&mut self, name: &str, request: Reservation, priorities: Option<PriorityRange>, elasticity: Option<Elasticity>, cpus: Option<Vec<CpuID>>, requester: &Requester) -> anyhow::Result<()> {
+        self.check_capabilities(&request, cpus.as_deref())?;
+
+        self.cgroups.create_cgroup(name, request, priorities, elasticity, cpus, requester)
+    }
+
+    /// Creates the cgroup with the largest runtime up to the requested one
+    /// which passes admission, and returns the granted reservation.
+    pub fn create_cgroup_best_effort(&mut self, name: &str, request: Reservation, priorities: Option<PriorityRange>, elasticity: Option<Elasticity>, cpus: Option<Vec<CpuID>>, requester: &Requester) -> anyhow::Result<Reservation> {
+        self.check_capabilities(&request, cpus.as_deref())?;
+
+        let runtime_us = u64::min(request.runtime_us, self.cgroups.max_admissible_runtime(name, request.period_us, cpus.as_deref(), requester));
+        if runtime_us == 0 {
+            anyhow::bail!("Cgroup {} cannot be allocated: no runtime can be granted with period {}us.", cgroup_abs_path(name), request.period_us);
+        }
+
+        let granted = Reservation { runtime_us, ..request };
+        self.cgroups.create_cgroup(name, granted, priorities, elasticity, cpus, requester)?;
+
+        if granted.runtime_us < request.runtime_us {
+            info!("Cgroup \"{name}\" granted {}us every {}us out of the requested {}us",
+                granted.runtime_us, granted.period_us, request.runtime_us);
+        }
+
+        Ok(granted)
+    }
+
+    pub fn update_cgroup(&mut self, name: &str, request: Reservation, priorities: Option<Option<PriorityRange>>, elasticity: Option<Option<Elasticity>>, cpus: Option<Vec<CpuID>>, requester: &Requester) -> anyhow::Result<()> {
+        self.check_capabilities(&request, cpus.as_deref())?;
+        self.cgroups.check_owner(name, requester)?;
+
+        self.cgroups.update_cgroup(name, request, priorities, elasticity, cpus)
+    }
+
+    /// Checks that the kernel supports the requested period and CPUs.
+    fn check_capabilities(&self, request: &Reservation, cpus: Option<&[CpuID]>) -> anyhow::Result<()> {
+        self.capabilities.check_period(request.period_us)?;
+
+        if let Some(cpus) = cpus {
+            self.capabilities.check_cpus(cpus)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sets the tasks running in the cgroup, which must be schedulable inside
+    /// its reservation. With the `analysis` admission policy, later updates of
+    /// the reservation must keep them schedulable.
+    pub fn set_cgroup_tasks(&mut self, name: &str, tasks: Vec<AnalysisTask>, requester: &Requester) -> anyhow::Result<()> {
+        self.cgroups.check_owner(name, requester)?;
+
+        self.cgroups.set_tasks(name, tasks)
     }
 
-    pub fn create_cgroup(&mut self, name: &str, request: Reservation) -> anyhow::Result<()> {
-        self.cgroups.create_cgroup(name, request)
+    /// Runs the admission test on the request without applying it. The
+    /// verdict is only recorded as the requester's last evaluation.
+    pub fn evaluate_admission(&mut self, name: &str, request: Reservation, requester: &Requester) -> anyhow::Result<Verdict> {
+        self.capabilities.check_period(request.period_us)?;
+
+        if self.cgroups.is_managed_cgroup(name) {
+            self.cgroups.check_owner(name, requester)?;
+        }
+
+        Ok(self.cgroups.evaluate(name, request, requester))
     }
 
-    pub fn update_cgroup(&mut self, name: &str, request: Reservation) -> anyhow::Result<()> {
-        self.cgroups.update_cgroup(name, request)
+    pub fn admission_policy(&self) -> &dyn AdmissionPolicy {
+        self.cgroups.admission_policy()
     }
 
-    pub fn destroy_cgroup(&mut self, name: &str) -> anyhow::Result<()> {
+    /// Last admission verdict of each cgroup name, with the requested
+    /// reservation.
+    pub fn admission_verdicts(&self) -> impl Iterator<Item = (&str, &Reservation, &Verdict)> {
+        self.cgroups.verdicts()
+    }
+
+    /// Last admission test run by the requester without applying it.
+    pub fn admission_evaluation(&self, requester: &Requester) -> Option<(&str, &Reservation, &Verdict)> {
+        self.cgroups.evaluation(requester.uid)
+    }
+
+    /// Computes the Pareto-optimal reservations making the task set
+    /// schedulable, within the periods supported by the kernel, and keeps the
+    /// ones passing admission as suggestions for the cgroup. With `create`,
+    /// the cgroup is created instead with the smallest admitted reservation,
+    /// together with the task set.
+    pub fn size_cgroup(&mut self, name: &str, tasks: Vec<AnalysisTask>, create: bool, requester: &Requester) -> anyhow::Result<()> {
+        let reservations: Vec<_> =
+            analysis::pareto_reservations(&tasks, self.capabilities.min_period_us, self.capabilities.max_period_us)
+            .into_iter()
+            .filter(|reservation| self.cgroups.admits(name, reservation, &tasks, requester))
+            .collect();
+
+        let Some(&reservation) = reservations.first()
+            else { anyhow::bail!("No admissible reservation makes the task set schedulable."); };
+
+        if create {
+            self.create_cgroup(name, reservation, None, None, None, requester)?;
+
+            // The reservation was computed for the task set, so a cgroup
+            // without it is of no use.
+            if let Err(err) = self.cgroups.set_tasks(name, tasks) {
+                if let Err(destroy_err) = self.cgroups.destroy_cgroup(name) {
+                    error!("Cannot destroy cgroup \"{name}\" after a failed sizing: {destroy_err}");
+                }
+
+                return Err(err);
+            }
+        }
+
+        self.sizings.insert(name.to_owned(), reservations);
+
+        Ok(())
+    }
+
+    pub fn sizings(&self) -> impl Iterator<Item = (&str, &[Reservation])> {
+        self.sizings.iter().map(|(name, reservations)| (name.as_str(), reservations.as_slice()))
+    }
+
+    /// Destroys the given managed cgroup. If the cgroup still has processes
+    /// and no eviction policy is given, the cgroup is not destroyed, and its
+    /// processes are left untouched. Cgroups can only be left on exit.
+    pub fn destroy_cgroup(&mut self, name: &str, eviction: Option<EvictionPolicy>, requester: &Requester) -> anyhow::Result<()> {
+        self.cgroups.check_owner(name, requester)?;
+        check_not_left(name, eviction)?;
+
+        if eviction.is_none() {
+            self.check_no_remaining_processes(name)?;
+        }
+
+        if self.procs.restore_policy() == RestorePolicy::Restore {
+            self.procs.release_processes_in_cgroup(name)?;
+        }
+
+        if let Some(policy) = eviction {
+            self.evict_cgroup(name, policy)?;
+        }
+
         self.cgroups.destroy_cgroup(name)
     }
 
+    /// Applies the operations all-or-nothing. Admission is checked on the
+    /// final state, then the steps are applied so that no intermediate state
+    /// needs more bandwidth than the final one. If a step fails, the applied
+    /// ones are undone in reverse order. The processes of deleted cgroups are
+    /// only evicted once every step is applied.
+    pub fn run_transaction(&mut self, mut ops: Vec<TransactionOp>, requester: &Requester) -> anyhow::Result<()> {
+        for op in ops.iter() {
+            match op {
+                TransactionOp::Create { request, cpus, .. } =>
+                    self.check_capabilities(request, cpus.as_deref())?,
+                TransactionOp::Update { name, request, cpus, .. } => {
+                    self.check_capabilities(request, cpus.as_deref())?;
+                    self.cgroups.check_owner(name, requester)?;
+                },
+                TransactionOp::Delete { name, eviction } => {
+                    self.cgroups.check_owner(name, requester)?;
+                    check_not_left(name, *eviction)?;
+                },
+            }
+        }
+
+        self.cgroups.check_transaction(&ops, requester)?;
+
+        ops.sort_by_key(|op| (op.phase(self.cgroups.get(op.name())), op.nesting_order()));
+
+        let mut applied = Vec::with_capacity(ops.len());
+        for op in ops.iter() {
+            if let Err(err) = self.apply_transaction_op(op, requester, &mut applied) {
+                self.rollback(applied);
+                anyhow::bail!("Transaction failed at {op}, rolled back: {err}");
+            }
+        }
+
+        for step in applied {
+            if let Applied::Deleted(name, _, detached, eviction) = step {
+                self.evict_detached(&name, detached, eviction);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn apply_transaction_op(&mut self, op: &TransactionOp, requester: &Requester, applied: &mut Vec<Applied>) -> anyhow::Result<()> {
+        match op {
+            TransactionOp::Create { name, request, priorities, elasticity, cpus, tasks } => {
+                self.cgroups.create_cgroup(name, *request, *priorities, *elasticity, cpus.clone(), requester)?;
+                applied.push(Applied::Created(name.clone()));
+
+                if let Some(tasks) = tasks {
+                    self.cgroups.set_tasks(name, tasks.clone())?;
+                }
+            },
+            TransactionOp::Update { name, request, priorities, elasticity, cpus, tasks } => {
+                let Some(data) = self.cgroups.get(name).cloned()
+                    else { anyhow::bail!("Cgroup {} does not exist.", cgroup_abs_path(name)); };
+                applied.push(Applied::Changed(name.clone(), data));
+
+                // The old task set may not fit in the new reservation, while
+                // the new one was already checked against it.
+                if tasks.is_some() {
+                    self.cgroups.set_tasks(name, Vec::new())?;
+                }
+
+                self.cgroups.update_cgroup(name, *request, *priorities, *elasticity, cpus.clone())?;
+
+                if let Some(tasks) = tasks {
+                    self.cgroups.set_tasks(name, tasks.clone())?;
+                }
+            },
+            TransactionOp::Delete { name, eviction } => {
+                let Some(data) = self.cgroups.get(name).cloned()
+                    else { anyhow::bail!("Cgroup {} does not exist.", cgroup_abs_path(name)); };
+
+                if eviction.is_none() {
+                    self.check_no_remaining_processes(name)?;
+                }
+
+                // Processes are only evicted once the transaction can no
+                // longer be rolled back.
+                let detached = detach_processes(name)?;
+                if let Err(err) = self.cgroups.destroy_cgroup(name) {
+                    reattach_processes(detached);
+                    return Err(err);
+                }
+
+                applied.push(Applied::Deleted(name.clone(), data, detached, *eviction));
+            },
+        }
+
+        Ok(())
+    }
+
+    fn rollback(&mut self, applied: Vec<Applied>) {
+        for step in applied.into_iter().rev() {
+            let result = match step {
+                Applied::Created(name) => self.cgroups.destroy_cgroup(&name),
+                Applied::Changed(name, data) => self.cgroups.restore_cgroup(&name, data),
+                Applied::Deleted(name, data, detached, _) => self.cgroups.restore_cgroup(&name, data)
+                    .map(|_| reattach_processes(detached)),
+            };
+
+            if let Err(err) = result {
+                error!("Cannot roll back transaction: {err}");
+            }
+        }
+    }
+
+    /// Sets the modes which can be switched to. The active mode is kept even
+    /// if no longer defined.
+    pub fn set_modes(&mut self, modes: Vec<Mode>) {
+        self.modes = modes;
+    }
+
+    pub fn modes(&self) -> impl Iterator<Item = &Mode> {
+        self.modes.iter()
+    }
+
+    pub fn active_mode(&self) -> Option<&str> {
+        self.active_mode.as_deref()
+    }
+
+    /// Switches to the given mode in a single transaction, which creates or
+    /// updates the cgroups of the mode and deletes the ones of the other
+    /// modes. Other cgroups are left untouched. Only root can switch modes.
+    pub fn switch_mode(&mut self, name: &str, requester: &Requester) -> anyhow::Result<()> {
+        if !requester.is_root() {
+            anyhow::bail!("Only root can switch modes: permission denied for user {}.", requester.uid);
+        }
+
+        let Some(mode) = self.modes.iter().find(|mode| mode.name == name).cloned()
+            else { anyhow::bail!("Mode \"{name}\" does not exist."); };
+
+        let mut deleted: Vec<String> =
+            self.modes.iter()
+            .filter(|other| other.name != mode.name)
+            .flat_map(|other| other.cgroups.iter())
+            .filter(|cgroup| !mode.contains(&cgroup.name) && self.cgroups.is_managed_cgroup(&cgroup.name))
+            .map(|cgroup| cgroup.name.clone())
+            .collect();
+        deleted.sort();
+        deleted.dedup();
+
+        let ops =
+            mode.cgroups.iter()
+            .map(|cgroup| {
+                let name = cgroup.name.clone();
+                let request = cgroup.reservation;
+                let tasks = Some(cgroup.tasks.clone());
+
+                if self.cgroups.is_managed_cgroup(&cgroup.name) {
+                    TransactionOp::Update { name, request, priorities: None, elasticity: None, cpus: None, tasks }
+                } else {
+                    TransactionOp::Create { name, request, priorities: None, elasticity: None, cpus: None, tasks }
+                }
+            })
+            .chain(deleted.into_iter().map(|name| TransactionOp::Delete { name, eviction: mode.eviction }))
+            .collect();
+
+        self.run_transaction(ops, requester)?;
+        self.active_mode = Some(mode.name);
+
+        info!("Switched to mode \"{name}\"");
+
+        Ok(())
+    }
+
+    /// Checks that releasing the managed processes empties the cgroup, i.e.
+    /// that it has no unmanaged processes, nor managed ones if they are kept
+    /// on deletion.
+    fn check_no_remaining_processes(&self, name: &str) -> anyhow::Result<()> {
+        let released = |pid| self.procs.restore_policy() == RestorePolicy::Restore && self.procs.is_managed_process(pid);
+
+        let remaining = crate::ops::cgroup_pids(name)?.into_iter().filter(|&pid| !released(pid)).count();
+        if remaining > 0 {
+            anyhow::bail!("Cgroup {} cannot be destroyed: it has {remaining} processes which would not be released, and no eviction policy was given.",
+                cgroup_abs_path(name));
+        }
+
+        Ok(())
+    }
+
+    /// Evicts the processes detached from a cgroup deleted by a transaction,
+    /// as [`Self::destroy_cgroup`] would have. They are already in the root
+    /// cgroup as SCHED_OTHER, i.e. demoted. Failures are only logged, as the
+    /// transaction is already applied.
+    fn evict_detached(&mut self, name: &str, detached: Vec<(Pid, TaskState)>, eviction: Option<EvictionPolicy>) {
+        let pids: Vec<Pid> = detached.into_iter().map(|(pid, _)| pid).collect();
+
+        let mut released = Vec::new();
+        if self.procs.restore_policy() == RestorePolicy::Restore || eviction == Some(EvictionPolicy::Original) {
+            let managed: Vec<Pid> = pids.iter().copied().filter(|&pid| self.procs.is_managed_process(pid)).collect();
+
+            if let Err(err) = self.procs.release_processes(&managed, name) {
+                error!("Cannot release the processes of deleted cgroup \"{name}\": {err}");
+            }
+
+            released = managed.into_iter().filter(|&pid| !self.procs.is_managed_process(pid)).collect();
+        }
+
+        if eviction == Some(EvictionPolicy::Kill) {
+            for pid in pids.into_iter().filter(|pid| !released.contains(pid)) {
+                if let Err(err) = crate::ops::kill_pid(pid) {
+                    error!("Cannot kill PID {pid} of deleted cgroup \"{name}\": {err}");
+                }
+            }
+        }
+    }
+
+    fn evict_cgroup(&mut self, name: &str, policy: EvictionPolicy) -> anyhow::Result<()> {
+        if policy == EvictionPolicy::Original {
+            self.procs.release_processes_in_cgroup(name)?;
+        }
+
+        self.cgroups.evict_cgroup(name, policy)
+    }
+
     pub fn is_managed_cgroup(&self, name: &str) -> bool {
         self.cgroups.is_managed_cgroup(name)
     }
 
-    pub fn assign_cgroup_to_process(&mut self, pid: Pid, cgroup: &str) -> anyhow::Result<()> {
-        self.procs.assign_cgroup_to_process(&self.cgroups, pid, cgroup)
+    pub fn managed_cgroups(&self) -> impl Iterator<Item = (&str, &CgroupData)> {
+        self.cgroups.cgroups()
+    }
+
+    pub fn managed_cgroup(&self, name: &str) -> Option<&CgroupData> {
+        self.cgroups.get(name)
+    }
+
+    /// CPUs running the reservations of the managed cgroups. Cgroups whose
+    /// cpuset cannot be read are assumed to run on every CPU.
+    pub fn reservation_cpus(&self) -> Vec<CpuID> {
+        let mut cpus: Vec<CpuID> =
+            self.cgroups.managed_cgroups()
+            .flat_map(|name| match crate::ops::cgroup_cpus(name) {
+                Ok(cpus) => cpus,
+                Err(err) => {
+                    warn!("Couldn't read the CPUs of cgroup \"{name}\": {err}");
+                    CpuSet::all().map(|cpus| cpus.iter().copied().collect()).unwrap_or_default()
+                },
+            })
+            .collect();
+
+        cpus.sort_unstable();
+        cpus.dedup();
+
+        cpus
+    }
+
+    /// Sets the authorization policy for process operations. Without a
+    /// policy, only the ownership of the target cgroups is checked.
+    pub fn set_auth_policy(&mut self, policy: Option<AuthPolicy>) {
+        self.auth_policy = policy;
+    }
+
+    pub fn set_quotas(&mut self, quotas: Vec<Quota>) {
+        self.cgroups.set_quotas(quotas)
+    }
+
+    pub fn quota_usages(&self) -> Vec<(QuotaSubject, QuotaUsage, Option<&Quota>)> {
+        self.cgroups.quota_usages()
+    }
+
+    /// Processes can only be moved into managed cgroups owned by the
+    /// requester, unless the requester is root.
+    pub fn assign_cgroup_to_process(&mut self, pid: Pid, cgroup: &str, scope: TaskScope, requester: &Requester) -> anyhow::Result<()> {
+        self.authorize(requester, pid, scope, |pid| Ok(Operation::MoveProcess { pid, cgroup }))?;
+
+        if self.cgroups.is_managed_cgroup(cgroup) {
+            self.cgroups.check_owner(cgroup, requester)?;
+        }
+
+        self.procs.assign_cgroup_to_process(&self.cgroups, pid, cgroup, scope)
+    }
+
+    pub fn set_process_sched_policy(&mut self, pid: Pid, request: PolicyRequest, scope: TaskScope, requester: &Requester) -> anyhow::Result<()> {
+        self.authorize(requester, pid, scope, |pid| Ok(Operation::SetPolicy {
+            pid,
+            policy: self.procs.effective_policy(&self.cgroups, &crate::ops::get_pid_cgroup(pid)?, &request)?,
+        }))?;
+
+        self.procs.set_process_sched_policy(&self.cgroups, pid, request, scope)
+    }
+
+    pub fn release_process(&mut self, pid: Pid, requester: &Requester) -> anyhow::Result<()> {
+        self.authorize(requester, pid, TaskScope::Process, |pid| Ok(Operation::Release { pid }))?;
+
+        self.procs.release_process(&self.cgroups, pid)
+    }
+
+    pub fn process_timing(&self, pid: Pid) -> Option<Timing> {
+        self.procs.process_timing(pid)
+    }
+
+    /// Registers the timing parameters of the process, whose priority is then
+    /// assigned automatically within its managed cgroup. The requester must be
+    /// allowed the top of the cgroup's priority range, which the process may
+    /// be assigned.
+    pub fn set_process_timing(&mut self, pid: Pid, timing: Option<Timing>, requester: &Requester) -> anyhow::Result<()> {
+        self.authorize(requester, pid, TaskScope::Process, |pid| Ok(Operation::SetTiming {
+            pid,
+            max_priority: match timing {
+                Some(_) => Some(self.timing_max_priority(pid)?),
+                None => None,
+            },
+        }))?;
+
+        self.procs.set_process_timing(&self.cgroups, pid, timing)
     }
 
-    pub fn set_process_sched_policy(&mut self, pid: Pid, policy: SchedPolicy) -> anyhow::Result<()> {
-        self.procs.set_process_sched_policy(&self.cgroups, pid, policy)
+    pub fn place_process(&mut self, pid: Pid, cgroup: &str, request: PolicyRequest, requester: &Requester) -> anyhow::Result<()> {
+        self.authorize(requester, pid, TaskScope::Process, |pid| Ok(Operation::MoveProcess { pid, cgroup }))?;
+        self.authorize(requester, pid, TaskScope::Process, |pid| Ok(Operation::SetPolicy {
+            pid,
+            policy: self.procs.effective_policy(&self.cgroups, cgroup, &request)?,
+        }))?;
+
+        if self.cgroups.is_managed_cgroup(cgroup) {
+            self.cgroups.check_owner(cgroup, requester)?;
+        }
+
+        if !requester.is_root() {
+            self.check_placement_source(pid)?;
+        }
+
+        self.procs.place_process(&self.cgroups, pid, cgroup, request)
+    }
+}
+
+impl HCBSManager {
+    /// Highest priority a process with timing parameters can be assigned. Out
+    /// of a managed cgroup, it can reach the top of any cgroup's range.
+    fn timing_max_priority(&self, pid: Pid) -> anyhow::Result<u8> {
+        let cgroup = crate::ops::get_pid_cgroup(pid)?;
+
+        Ok(self.cgroups.get(&cgroup)
+            .map_or(PriorityRange::FULL, |data| data.priority_range())
+            .max)
+    }
+
+    /// Real-time processes can only be pulled out of the managed cgroups, or
+    /// the root one, as other software may rely on where it placed them.
+    fn check_placement_source(&self, pid: Pid) -> anyhow::Result<()> {
+        let source = crate::ops::get_pid_cgroup(pid)?;
+        if source == ROOT_CGROUP || self.cgroups.is_managed_cgroup(&source) {
+            return Ok(());
+        }
+
+        if !crate::ops::get_sched_policy(pid)?.is_other() {
+            anyhow::bail!("PID {pid} is a real-time process in cgroup \"{source}\", which is not managed by this controller: only root can move it.");
+        }
+
+        Ok(())
+    }
+
+    /// Checks the operation against the authorization policy, for every task
+    /// in the scope.
+    fn authorize<'a, F>(&self, requester: &Requester, pid: Pid, scope: TaskScope, op: F) -> anyhow::Result<()>
+        where F: Fn(Pid) -> anyhow::Result<Operation<'a>>
+    {
+        let Some(policy) = &self.auth_policy
+            else { return Ok(()); };
+
+        for task in scope.tasks(pid)? {
+            policy.authorize(requester, op(task)?)?;
+        }
+
+        Ok(())
     }
 }
 
 impl Drop for HCBSManager {
     fn drop(&mut self) {
+        // Nested cgroups are evicted, and destroyed, before their parents.
+        let mut cgroups: Vec<_> =
+            self.cgroups.managed_cgroups()
+            .map(|name| name.to_owned())
+            .collect();
+        cgroups.sort_by_key(|name| std::cmp::Reverse(cgroup_depth(name)));
+
+        if self.eviction_policy == EvictionPolicy::Original {
+            for name in cgroups.iter() {
+                if let Err(err) = self.procs.release_processes_in_cgroup(name) {
+                    error!("Error in restoring processes of cgroup \"{name}\": {err}");
+                }
+            }
+        }
+
         std::mem::take(&mut self.procs);
+
+        for name in cgroups.iter() {
+            if let Err(err) = self.cgroups.evict_cgroup(name, self.eviction_policy) {
+                error!("Error in evicting cgroup \"{name}\": {err}");
+            }
+        }
+
         std::mem::take(&mut self.cgroups);
+        std::mem::take(&mut self.rules);
     }
-}
\ No newline at end of file
+}
+/// Rules place processes with the manager's privileges, so only root can
+/// change them.
+fn check_rules_access(requester: &Requester) -> anyhow::Result<()> {
+    if !requester.is_root() {
+        anyhow::bail!("Only root can change the placement rules: permission denied for user {}.", requester.uid);
+    }
+
+    Ok(())
+}
+
+/// Left cgroups keep their bandwidth outside of admission, so they are only
+/// left when the manager exits.
+fn check_not_left(name: &str, eviction: Option<EvictionPolicy>) -> anyhow::Result<()> {
+    if eviction == Some(EvictionPolicy::Leave) {
+        anyhow::bail!("Cgroup {} cannot be left on deletion, as it would keep its bandwidth: \"leave\" only applies on exit.",
+            cgroup_abs_path(name));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,425 @@
+use std::collections::HashMap;
+
+use hcbs_utils::prelude::*;
+
+use super::{AnalysisTask, Reservation, analysis};
+
+/// Bandwidth cap of the managed reservations, leaving some room for the rest
+/// of the system.
+pub const MAX_RESOURCE: f64 = 0.95;
+
+/// Admission test of the cgroup reservations. Quotas are checked separately,
+/// whatever the policy.
+pub trait AdmissionPolicy: std::fmt::Debug + Send {
+    fn name(&self) -> &'static str;
+
+    /// Checks the requested allocation of a cgroup against the allocations
+    /// of the other managed cgroups.
+    fn admit(&self, request: &Allocation, others: &[Allocation]) -> Verdict;
+
+    /// Groups of cgroups sharing the same capacity, each with the bandwidth
+    /// their reservations leave under it. Elastic cgroups can only grow into
+    /// the spare bandwidth of every group they belong to.
+    fn spare<'a>(&self, cgroups: &[Allocation<'a>]) -> Vec<(Vec<&'a str>, f64)> {
+        let requested: f64 = cgroups.iter().map(|cgroup| cgroup.reservation.bandwidth()).sum();
+
+        vec![(
+            cgroups.iter().map(|cgroup| cgroup.name).collect(),
+            f64::max(0.0, MAX_RESOURCE - requested),
+        )]
+    }
+}
+
+/// Reservation of a cgroup, with what the policies may look at besides its
+/// bandwidth.
+#[derive(Debug, Clone, Copy)]
+pub struct Allocation<'a> {
+    pub name: &'a str,
+    pub reservation: Reservation,
+    /// CPUs the cgroup is restricted to, every CPU if `None`.
+    pub cpus: Option<&'a [CpuID]>,
+    /// Tasks running in the cgroup, empty if not described.
+    pub tasks: &'a [AnalysisTask],
+}
+
+/// Outcome of an admission test. The headroom is the largest bandwidth the
+/// cgroup could be granted, `None` if unbounded.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Accepted { headroom: Option<f64> },
+    Rejected { reason: String, headroom: Option<f64> },
+}
+
+/// Built-in admission policies, selected on startup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum AdmissionKind {
+    /// The total bandwidth must fit under the cap.
+    #[default]
+    Utilisation,
+    /// Nested cgroups must fit in the reservation of their managed parent,
+    /// and the top-level ones under the cap.
+    Hierarchical,
+    /// Every request is accepted, for experiments.
+    AcceptAll,
+    /// The bandwidth of the cgroups running on each CPU must fit under the
+    /// cap, following their cpusets.
+    PerCpu,
+    /// The total bandwidth must fit under the cap, and the task set of the
+    /// cgroup must be schedulable in its reservation.
+    Analysis,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct UtilisationPolicy;
+
+#[derive(Debug, Clone, Copy)]
+pub struct HierarchicalPolicy;
+
+#[derive(Debug, Clone, Copy)]
+pub struct AcceptAllPolicy;
+
+#[derive(Debug, Clone, Copy)]
+pub struct PerCpuPolicy;
+
+#[derive(Debug, Clone, Copy)]
+pub struct AnalysisPolicy;
+
+impl Verdict {
+    pub fn is_accepted(&self) -> bool {
+        matches!(self, Verdict::Accepted { .. })
+    }
+
+    pub fn headroom(&self) -> Option<f64> {
+        match self {
+            Verdict::Accepted { headroom } | Verdict::Rejected { headroom, .. } => *headroom,
+        }
+    }
+
+    /// Rejects the request with the given reason, or keeps an earlier
+    /// rejection. The headroom is the smallest of the two.
+    pub fn reject(self, reason: String, headroom: Option<f64>) -> Self {
+        let headroom = match (self.headroom(), headroom) {
+            (Some(a), Some(b)) => Some(f64::min(a, b)),
+            (a, b) => a.or(b),
+        };
+
+        match self {
+            Verdict::Accepted { .. } => Verdict::Rejected { reason, headroom },
+            Verdict::Rejected { reason, .. } => Verdict::Rejected { reason, headroom },
+        }
+    }
+
+    /// Lowers the headroom, e.g. to account for a quota.
+    pub fn limit(self, limit: f64) -> Self {
+        let headroom = Some(self.headroom().map_or(limit, |headroom| f64::min(headroom, limit)));
+
+        match self {
+            Verdict::Accepted { .. } => Verdict::Accepted { headroom },
+            Verdict::Rejected { reason, .. } => Verdict::Rejected { reason, headroom },
+        }
+    }
+}
+
+impl std::fmt::Display for Verdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let headroom = self.headroom()
+            .map_or_else(|| "-".to_owned(), |headroom| format!("{headroom:.3}"));
+
+        match self {
+            Verdict::Accepted { .. } => write!(f, "accepted headroom {headroom}"),
+            Verdict::Rejected { reason, .. } => write!(f, "rejected headroom {headroom} {reason}"),
+        }
+    }
+}
+
+impl AdmissionKind {
+    pub fn policy(self) -> Box<dyn AdmissionPolicy> {
+        match self {
+            AdmissionKind::Utilisation => Box::new(UtilisationPolicy),
+            AdmissionKind::Hierarchical => Box::new(HierarchicalPolicy),
+            AdmissionKind::AcceptAll => Box::new(AcceptAllPolicy),
+            AdmissionKind::PerCpu => Box::new(PerCpuPolicy),
+            AdmissionKind::Analysis => Box::new(AnalysisPolicy),
+        }
+    }
+}
+
+impl AdmissionPolicy for UtilisationPolicy {
+    fn name(&self) -> &'static str {
+        "utilisation"
+    }
+
+    fn admit(&self, request: &Allocation, others: &[Allocation]) -> Verdict {
+        let allocated: f64 = others.iter().map(|other| other.reservation.bandwidth()).sum();
+
+        fits(&request.reservation, MAX_RESOURCE, allocated, "total")
+    }
+}
+
+impl AdmissionPolicy for HierarchicalPolicy {
+    fn name(&self) -> &'static str {
+        "hierarchical"
+    }
+
+    fn admit(&self, request: &Allocation, others: &[Allocation]) -> Verdict {
+        // The requested cgroup may be the parent of some of the others.
+        let all: Vec<Allocation> = others.iter().copied().chain([*request]).collect();
+
+        let parent = parent_of(request.name, others);
+        let (capacity, level) = match parent {
+            Some(parent) => (parent.reservation.bandwidth(), format!("cgroup \"{}\"", parent.name)),
+            None => (MAX_RESOURCE, "top-level".to_owned()),
+        };
+
+        let siblings: f64 =
+            others.iter()
+            .filter(|other| parent_of(other.name, &all).map(|parent| parent.name) == parent.map(|parent| parent.name))
+            .map(|other| other.reservation.bandwidth())
+            .sum();
+
+        let verdict = fits(&request.reservation, capacity, siblings, &level);
+
+        let children: f64 =
+            others.iter()
+            .filter(|other| parent_of(other.name, &all).is_some_and(|parent| parent.name == request.name))
+            .map(|other| other.reservation.bandwidth())
+            .sum();
+
+        if request.reservation.bandwidth() < children {
+            return verdict.reject(
+                format!("Nested cgroups need bandwidth {children:.3}, more than the requested {:.3}.", request.reservation.bandwidth()),
+                None);
+        }
+
+        verdict
+    }
+
+    /// Each managed cgroup shares its reservation among its nested cgroups,
+    /// and the top-level ones share the cap.
+    fn spare<'a>(&self, cgroups: &[Allocation<'a>]) -> Vec<(Vec<&'a str>, f64)> {
+        let mut groups: HashMap<Option<&str>, Vec<&'a str>> = HashMap::new();
+        for cgroup in cgroups.iter() {
+            let parent = parent_of(cgroup.name, cgroups).map(|parent| parent.name);
+            groups.entry(parent).or_default().push(cgroup.name);
+        }
+
+        let bandwidth = |name: &str| {
+            cgroups.iter()
+                .find(|cgroup| cgroup.name == name)
+                .map_or(0.0, |cgroup| cgroup.reservation.bandwidth())
+        };
+
+        groups.into_iter()
+            .map(|(parent, members)| {
+                let capacity = parent.map_or(MAX_RESOURCE, bandwidth);
+                let requested: f64 = members.iter().map(|name| bandwidth(name)).sum();
+
+                (members, f64::max(0.0, capacity - requested))
+            })
+            .collect()
+    }
+}
+
+impl AdmissionPolicy for AcceptAllPolicy {
+    fn name(&self) -> &'static str {
+        "accept-all"
+    }
+
+    fn admit(&self, _request: &Allocation, _others: &[Allocation]) -> Verdict {
+        Verdict::Accepted { headroom: None }
+    }
+}
+
+impl AdmissionPolicy for PerCpuPolicy {
+    fn name(&self) -> &'static str {
+        "per-cpu"
+    }
+
+    /// Only the CPUs some cgroup is restricted to can be more loaded than the
+    /// others, which all run the same unrestricted cgroups.
+    fn admit(&self, request: &Allocation, others: &[Allocation]) -> Verdict {
+        let cpus = match request.cpus {
+            Some(cpus) => cpus.to_vec(),
+            None => restricted_cpus(others),
+        };
+
+        let unrestricted = request.cpus.is_none().then(|| ("every CPU".to_owned(), cpu_load(others, None)));
+        let (level, allocated) =
+            cpus.into_iter()
+            .map(|cpu| (format!("CPU {cpu}"), cpu_load(others, Some(cpu))))
+            .chain(unrestricted)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap_or_else(|| ("every CPU".to_owned(), 0.0));
+
+        fits(&request.reservation, MAX_RESOURCE, allocated, &level)
+    }
+
+    /// The cgroups running on each CPU share its cap.
+    fn spare<'a>(&self, cgroups: &[Allocation<'a>]) -> Vec<(Vec<&'a str>, f64)> {
+        restricted_cpus(cgroups).into_iter()
+            .map(Some)
+            .chain([None])
+            .map(|cpu| {
+                let members =
+                    cgroups.iter()
+                    .filter(|cgroup| runs_on(cgroup, cpu))
+                    .map(|cgroup| cgroup.name)
+                    .collect();
+
+                (members, f64::max(0.0, MAX_RESOURCE - cpu_load(cgroups, cpu)))
+            })
+            .collect()
+    }
+}
+
+impl AdmissionPolicy for AnalysisPolicy {
+    fn name(&self) -> &'static str {
+        "analysis"
+    }
+
+    fn admit(&self, request: &Allocation, others: &[Allocation]) -> Verdict {
+        let verdict = UtilisationPolicy.admit(request, others);
+
+        match analysis::check_schedulable(request.tasks, request.reservation) {
+            Ok(()) => verdict,
+            Err(err) => verdict.reject(err.to_string(), None),
+        }
+    }
+}
+
+/// The closest managed ancestor of the cgroup, if any.
+fn parent_of<'a, 'b>(name: &str, cgroups: &'b [Allocation<'a>]) -> Option<&'b Allocation<'a>> {
+    ancestors(name)
+        .find_map(|ancestor| cgroups.iter().find(|cgroup| cgroup.name == ancestor))
+}
+
+/// CPUs some cgroup is restricted to, sorted.
+fn restricted_cpus(cgroups: &[Allocation]) -> Vec<CpuID> {
+    let mut cpus: Vec<CpuID> =
+        cgroups.iter()
+        .flat_map(|cgroup| cgroup.cpus.unwrap_or_default())
+        .copied()
+        .collect();
+    cpus.sort();
+    cpus.dedup();
+
+    cpus
+}
+
+/// Whether the cgroup runs on the CPU, where `None` stands for the CPUs no
+/// cgroup is restricted to.
+fn runs_on(cgroup: &Allocation, cpu: Option<CpuID>) -> bool {
+    match (cgroup.cpus, cpu) {
+        (None, _) => true,
+        (Some(cpus), Some(cpu)) => cpus.contains(&cpu),
+        (Some(_), None) => false,
+    }
+}
+
+/// Bandwidth of the cgroups running on the CPU, as for [`runs_on`].
+fn cpu_load(cgroups: &[Allocation], cpu: Option<CpuID>) -> f64 {
+    cgroups.iter()
+        .filter(|cgroup| runs_on(cgroup, cpu))
+        .map(|cgroup| cgroup.reservation.bandwidth())
+        .sum()
+}
+
+/// Ancestors of the cgroup, from the closest one.
+fn ancestors(name: &str) -> impl Iterator<Item = &str> {
+    std::iter::successors(name.rsplit_once('/'), |&(parent, _)| parent.rsplit_once('/'))
+        .map(|(parent, _)| parent)
+}
+
+fn fits(request: &Reservation, capacity: f64, allocated: f64, level: &str) -> Verdict {
+    let headroom = Some(f64::max(0.0, capacity - allocated));
+
+    if request.bandwidth() + allocated > capacity {
+        return Verdict::Rejected {
+            reason: format!("Insufficient {level} bandwidth: requested {:.3}, {:.3} available out of {capacity:.3}.",
+                request.bandwidth(), f64::max(0.0, capacity - allocated)),
+            headroom,
+        };
+    }
+
+    Verdict::Accepted { headroom }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allocation(name: &str, runtime_us: u64) -> Allocation<'_> {
+        Allocation { name, reservation: Reservation { runtime_us, period_us: 10000 }, cpus: None, tasks: &[] }
+    }
+
+    fn assert_headroom(verdict: &Verdict, expected: f64) {
+        let headroom = verdict.headroom().unwrap();
+        assert!((headroom - expected).abs() < 1e-9, "headroom {headroom}, expected {expected}");
+    }
+
+    /// `team` holds half a CPU, of which `team/a` uses 0.2, and `other` 0.3.
+    fn nested() -> Vec<Allocation<'static>> {
+        vec![allocation("team", 5000), allocation("team/a", 2000), allocation("other", 3000)]
+    }
+
+    #[test]
+    fn hierarchical_nested_cgroups_fit_in_their_parent() {
+        let others = nested();
+
+        let verdict = HierarchicalPolicy.admit(&allocation("team/b", 2000), &others);
+        assert!(verdict.is_accepted());
+        assert_headroom(&verdict, 0.3);
+
+        let verdict = HierarchicalPolicy.admit(&allocation("team/b", 4000), &others);
+        assert!(!verdict.is_accepted());
+        assert_headroom(&verdict, 0.3);
+
+        // The closest managed ancestor is `team/a`.
+        assert!(!HierarchicalPolicy.admit(&allocation("team/a/x", 3000), &others).is_accepted());
+    }
+
+    #[test]
+    fn hierarchical_top_level_cgroups_fit_under_the_cap() {
+        let others = nested();
+
+        let verdict = HierarchicalPolicy.admit(&allocation("new", 2000), &others);
+        assert!(!verdict.is_accepted());
+        assert_headroom(&verdict, MAX_RESOURCE - 0.8);
+
+        assert!(HierarchicalPolicy.admit(&allocation("new", 1000), &others).is_accepted());
+    }
+
+    #[test]
+    fn hierarchical_parent_cannot_shrink_below_its_children() {
+        let others: Vec<_> = nested().into_iter().filter(|other| other.name != "team").collect();
+
+        assert!(!HierarchicalPolicy.admit(&allocation("team", 1000), &others).is_accepted());
+        assert!(HierarchicalPolicy.admit(&allocation("team", 2000), &others).is_accepted());
+    }
+
+    #[test]
+    fn hierarchical_spare_is_grouped_per_parent() {
+        let mut groups = HierarchicalPolicy.spare(&nested());
+        groups.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].0, vec!["team", "other"]);
+        assert!((groups[0].1 - (MAX_RESOURCE - 0.8)).abs() < 1e-9);
+        assert_eq!(groups[1].0, vec!["team/a"]);
+        assert!((groups[1].1 - 0.3).abs() < 1e-9);
+    }
+
+    #[test]
+    fn per_cpu_load_follows_cpusets() {
+        let cpus = [1];
+        let others = [
+            Allocation { cpus: Some(&cpus[..]), ..allocation("pinned", 6000) },
+            allocation("everywhere", 3000),
+        ];
+
+        // CPU 1 runs both cgroups, the other CPUs only the unrestricted one.
+        assert!(!PerCpuPolicy.admit(&allocation("new", 1000), &others).is_accepted());
+        assert!(PerCpuPolicy.admit(&Allocation { cpus: Some(&[2, 3][..]), ..allocation("new", 6000) }, &others).is_accepted());
+        assert!(!UtilisationPolicy.admit(&Allocation { cpus: Some(&[2, 3][..]), ..allocation("new", 6000) }, &others).is_accepted());
+    }
+}
@@ -0,0 +1,211 @@
+use super::Reservation;
+
+/// Periodic task running inside a reservation, as described by its owner for
+/// the schedulability analysis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnalysisTask {
+    pub wcet_us: u64,
+    pub period_us: u64,
+    pub deadline_us: u64,
+    /// Fixed priority, higher values mean higher priorities as for SCHED_FIFO.
+    pub priority: u8,
+}
+
+impl AnalysisTask {
+    pub fn new(wcet_us: u64, period_us: u64, deadline_us: u64, priority: u8) -> anyhow::Result<Self> {
+        if wcet_us == 0 || wcet_us > deadline_us || deadline_us > period_us {
+            anyhow::bail!("Invalid task {wcet_us},{period_us},{deadline_us}: must be 0 < C <= D <= T.");
+        }
+
+        Ok(Self { wcet_us, period_us, deadline_us, priority })
+    }
+}
+
+/// Worst-case response time of each task when scheduled by fixed priorities
+/// inside the given reservation, or `None` if the task misses its deadline.
+/// Tasks with the same priority are assumed to interfere with each other.
+pub fn response_times(tasks: &[AnalysisTask], reservation: Reservation) -> Vec<Option<u64>> {
+    tasks.iter().enumerate()
+        .map(|(i, task)| {
+            let interfering: Vec<_> =
+                tasks.iter().enumerate()
+                .filter(|&(j, other)| j != i && other.priority >= task.priority)
+                .map(|(_, other)| other)
+                .collect();
+
+            response_time(task, &interfering, reservation)
+        })
+        .collect()
+}
+
+/// Checks that every task meets its deadline inside the reservation.
+pub fn check_schedulable(tasks: &[AnalysisTask], reservation: Reservation) -> anyhow::Result<()> {
+    let response_times = response_times(tasks, reservation);
+
+    if let Some((task, _)) = tasks.iter().zip(response_times).find(|(_, response_time)| response_time.is_none()) {
+        anyhow::bail!("Task {},{},{},{} misses its deadline with a reservation of {}us every {}us.",
+            task.wcet_us, task.period_us, task.deadline_us, task.priority,
+            reservation.runtime_us, reservation.period_us);
+    }
+
+    Ok(())
+}
+
+/// Fixed point iteration of the demand of the task and of the interfering
+/// tasks against the supply of the reservation.
+fn response_time(task: &AnalysisTask, interfering: &[&AnalysisTask], reservation: Reservation) -> Option<u64> {
+    let demand = |t: u64| -> u64 {
+        task.wcet_us +
+        interfering.iter()
+            .map(|other| t.div_ceil(other.period_us) * other.wcet_us)
+            .sum::<u64>()
+    };
+
+    let mut t = inverse_sbf(task.wcet_us, reservation)?;
+    loop {
+        if t > task.deadline_us {
+            return None;
+        }
+
+        let next = inverse_sbf(demand(t), reservation)?;
+        if next == t {
+            return Some(t);
+        }
+
+        t = next;
+    }
+}
+
+/// Smallest interval in which the reservation surely supplies the given
+/// amount of time. This inverts the supply bound function of a periodic
+/// server with budget Q and period P, whose worst case starts with a blackout
+/// of 2(P - Q):
+///
+///   sbf(t) = y Q + max(0, t - 2(P - Q) - y P),  y = floor((t - (P - Q)) / P)
+fn inverse_sbf(supply: u64, reservation: Reservation) -> Option<u64> {
+    let Reservation { runtime_us: budget, period_us: period } = reservation;
+
+    if supply == 0 {
+        return Some(0);
+    }
+
+    if budget == 0 {
+        return None;
+    }
+
+    let full_periods = supply.div_ceil(budget) - 1;
+    let remainder = supply - full_periods * budget;
+
+    Some(2 * period.saturating_sub(budget) + full_periods * period + remainder)
+}
+
+/// Pareto-optimal reservations for the task set, by increasing bandwidth: a
+/// reservation is kept only if no other one needs less bandwidth with an
+/// equal or longer period, as longer periods mean less scheduling overhead.
+/// Candidate periods are fractions of the shortest deadline, within the given
+/// bounds, each paired with the minimal runtime making the task set
+/// schedulable.
+pub fn pareto_reservations(tasks: &[AnalysisTask], min_period_us: u64, max_period_us: u64) -> Vec<Reservation> {
+    const CANDIDATE_PERIODS: u64 = 64;
+
+    let Some(min_deadline_us) = tasks.iter().map(|task| task.deadline_us).min()
+        else { return Vec::new(); };
+
+    let mut periods: Vec<u64> =
+        (1..=CANDIDATE_PERIODS)
+        .map(|fraction| min_deadline_us / fraction)
+        .filter(|period_us| (min_period_us..=max_period_us).contains(period_us))
+        .collect();
+    periods.dedup();
+
+    let mut pareto: Vec<Reservation> = Vec::new();
+    for period_us in periods {
+        let Some(runtime_us) = minimal_runtime(tasks, period_us)
+            else { continue; };
+
+        let reservation = Reservation { runtime_us, period_us };
+        if pareto.iter().all(|other| reservation.bandwidth() < other.bandwidth()) {
+            pareto.push(reservation);
+        }
+    }
+
+    pareto.reverse();
+    pareto
+}
+
+/// Smallest runtime making the task set schedulable with the given period.
+/// Schedulability only improves with a larger runtime, so it is found by
+/// binary search.
+fn minimal_runtime(tasks: &[AnalysisTask], period_us: u64) -> Option<u64> {
+    let schedulable = |runtime_us: u64|
+        response_times(tasks, Reservation { runtime_us, period_us })
+        .iter().all(Option::is_some);
+
+    if !schedulable(period_us) {
+        return None;
+    }
+
+    let (mut low, mut high) = (0, period_us);
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+
+        if schedulable(mid) { high = mid; }
+        else { low = mid; }
+    }
+
+    Some(high)
+}
+
+impl std::str::FromStr for AnalysisTask {
+    type Err = anyhow::Error;
+
+    /// Parses `<wcet us>,<period us>,<deadline us>,<priority>`.
+    fn from_str(data: &str) -> anyhow::Result<Self> {
+        use nom::Parser as _;
+
+        nom::combinator::all_consuming(crate::filesystem::parser::parse_analysis_task)
+            .parse(data)
+            .map(|(_, task)| task)
+            .map_err(|_| anyhow::format_err!("Invalid task \"{data}\": expected <wcet us>,<period us>,<deadline us>,<priority>"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(reservations: &[Reservation]) -> Vec<(u64, u64)> {
+        reservations.iter().map(|reservation| (reservation.runtime_us, reservation.period_us)).collect()
+    }
+
+    /// A single task of 1ms every 10ms. With period P, the task meets its
+    /// deadline if 2(P - Q) + 1000 <= 10000 while Q >= 1000, so the minimal
+    /// runtimes are Q = 5500 for P = 10000 and Q = 1000 for P = 5000.
+    #[test]
+    fn pareto_reservations_of_single_task() {
+        let tasks = [AnalysisTask::new(1000, 10000, 10000, 50).unwrap()];
+
+        assert_eq!(pairs(&pareto_reservations(&tasks, 4000, 10000)), vec![(1000, 5000), (5500, 10000)]);
+    }
+
+    #[test]
+    fn pareto_reservations_within_period_bounds() {
+        let tasks = [AnalysisTask::new(1000, 10000, 10000, 50).unwrap()];
+
+        assert_eq!(pairs(&pareto_reservations(&tasks, 10000, 10000)), vec![(5500, 10000)]);
+        assert!(pareto_reservations(&[], 1000, 10000).is_empty());
+    }
+
+    #[test]
+    fn response_times_count_interference() {
+        let tasks = [
+            AnalysisTask::new(1000, 10000, 10000, 50).unwrap(),
+            AnalysisTask::new(1000, 20000, 20000, 40).unwrap(),
+        ];
+
+        // Full budget: no blackout, the lower priority task waits for the
+        // higher priority one.
+        assert_eq!(response_times(&tasks, Reservation { runtime_us: 10000, period_us: 10000 }), vec![Some(1000), Some(2000)]);
+        assert!(check_schedulable(&tasks, Reservation { runtime_us: 1000, period_us: 10000 }).is_err());
+    }
+}
@@ -0,0 +1,242 @@
+use hcbs_utils::prelude::*;
+
+/// Process issuing a request to the manager, as reported by FUSE.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Requester {
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: Pid,
+}
+
+impl Requester {
+    /// The manager itself, e.g. when applying rules.
+    pub fn manager() -> Self {
+        Self {
+            uid: 0,
+            gid: 0,
+            pid: std::process::id(),
+        }
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.uid == 0
+    }
+}
+
+/// Authorization policy for process operations, loaded from a TOML file.
+/// Root is always allowed, while other users need an entry allowing the
+/// operation. Entries are matched on the requester's uid or primary gid.
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthPolicy {
+    pub allow: Vec<PolicyEntry>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PolicyEntry {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// Processes which can be changed.
+    #[serde(default)]
+    pub processes: ProcessOwner,
+    /// Cgroups processes can be moved into. A trailing `*` matches any
+    /// suffix, e.g. `team_a/*`.
+    #[serde(default)]
+    pub cgroups: Vec<String>,
+    /// Maximum SCHED_FIFO/SCHED_RR priority which can be set. Real-time
+    /// policies are not allowed if not given.
+    pub max_priority: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProcessOwner {
+    /// Only processes of the requester's user.
+    #[default]
+    Own,
+    Any,
+}
+
+/// Process operation to authorize.
+#[derive(Debug, Clone)]
+pub enum Operation<'a> {
+    MoveProcess { pid: Pid, cgroup: &'a str },
+    /// The policy with the absolute priority which is applied, once relative
+    /// priorities are resolved and clamped into the cgroup's range.
+    SetPolicy { pid: Pid, policy: SchedPolicy },
+    Release { pid: Pid },
+    /// Registers timing parameters, with the highest priority the process
+    /// could be assigned, or clears them.
+    SetTiming { pid: Pid, max_priority: Option<u8> },
+}
+
+impl AuthPolicy {
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+
+        let data = std::fs::read_to_string(path)
+            .map_err(|err| anyhow::format_err!("Cannot read policy file {}: {err}", path.display()))?;
+
+        let policy: Self = toml::from_str(&data)
+            .map_err(|err| anyhow::format_err!("Invalid policy file {}: {err}", path.display()))?;
+
+        if let Some(entry) = policy.allow.iter().find(|entry| entry.uid.is_some() == entry.gid.is_some()) {
+            anyhow::bail!("Invalid policy file {}: exactly one of uid and gid must be given ({entry:?})", path.display());
+        }
+
+        Ok(policy)
+    }
+
+    /// Checks whether the requester can perform the operation. Denied
+    /// requests are logged.
+    pub fn authorize(&self, requester: &Requester, op: Operation) -> anyhow::Result<()> {
+        if requester.is_root() {
+            return Ok(());
+        }
+
+        let result = self.check(requester, &op);
+        if let Err(err) = &result {
+            warn!("Denied {op:?} to uid {} (PID {}): {err}", requester.uid, requester.pid);
+        }
+
+        result
+    }
+
+    fn check(&self, requester: &Requester, op: &Operation) -> anyhow::Result<()> {
+        let pid = match *op {
+            Operation::MoveProcess { pid, .. } |
+            Operation::SetPolicy { pid, .. } |
+            Operation::Release { pid } |
+            Operation::SetTiming { pid, .. } => pid,
+        };
+
+        let target_uid = process_uid(pid)?;
+
+        let allowed =
+            self.allow.iter()
+            .filter(|entry| entry.matches(requester))
+            .filter(|entry| entry.processes == ProcessOwner::Any || target_uid == requester.uid)
+            .any(|entry| match op {
+                Operation::MoveProcess { cgroup, .. } => entry.allows_cgroup(cgroup),
+                Operation::SetPolicy { policy, .. } => entry.allows_policy(policy),
+                Operation::SetTiming { max_priority: Some(max_priority), .. } =>
+                    entry.allows_policy(&SchedPolicy::FIFO(*max_priority)),
+                Operation::Release { .. } |
+                Operation::SetTiming { max_priority: None, .. } => true,
+            });
+
+        if !allowed {
+            anyhow::bail!("Operation not allowed by the authorization policy.");
+        }
+
+        Ok(())
+    }
+}
+
+impl PolicyEntry {
+    fn matches(&self, requester: &Requester) -> bool {
+        self.uid == Some(requester.uid) || self.gid == Some(requester.gid)
+    }
+
+    fn allows_cgroup(&self, cgroup: &str) -> bool {
+        cgroup == ROOT_CGROUP ||
+        self.cgroups.iter().any(|pattern| match pattern.strip_suffix('*') {
+            Some(prefix) => cgroup.starts_with(prefix),
+            None => cgroup == pattern,
+        })
+    }
+
+    fn allows_policy(&self, policy: &SchedPolicy) -> bool {
+        match policy {
+            SchedPolicy::FIFO(prio) | SchedPolicy::RR(prio) =>
+                self.max_priority.is_some_and(|max| *prio <= max),
+            _ => true,
+        }
+    }
+}
+
+/// Owner of the given process, or thread.
+fn process_uid(pid: Pid) -> anyhow::Result<u32> {
+    use std::os::unix::fs::MetadataExt as _;
+
+    Ok(std::fs::metadata(format!("/proc/{pid}"))
+        .map_err(|err| anyhow::format_err!("Couldn't get the owner of PID {pid}: {err}"))?
+        .uid())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Requester owning the test process, so that `Own` entries match it.
+    fn requester() -> Requester {
+        let pid = std::process::id();
+
+        Requester { uid: process_uid(pid).unwrap(), gid: u32::MAX, pid }
+    }
+
+    fn policy(max_priority: Option<u8>) -> AuthPolicy {
+        AuthPolicy {
+            allow: vec![PolicyEntry {
+                uid: Some(requester().uid),
+                gid: None,
+                processes: ProcessOwner::Own,
+                cgroups: Vec::new(),
+                max_priority,
+            }],
+        }
+    }
+
+    #[test]
+    fn timing_is_checked_against_the_priority_it_can_get() {
+        let requester = requester();
+        let op = |max_priority| Operation::SetTiming { pid: requester.pid, max_priority };
+
+        assert!(policy(None).check(&requester, &op(Some(10))).is_err());
+        assert!(policy(Some(50)).check(&requester, &op(Some(98))).is_err());
+        assert!(policy(Some(50)).check(&requester, &op(Some(50))).is_ok());
+
+        // Clearing the timing parameters demotes the process.
+        assert!(policy(None).check(&requester, &op(None)).is_ok());
+    }
+
+    #[test]
+    fn cgroup_patterns() {
+        let entry = PolicyEntry {
+            cgroups: vec!["camera".to_owned(), "team_a/*".to_owned()],
+            ..policy(None).allow.remove(0)
+        };
+
+        assert!(entry.allows_cgroup("camera"));
+        assert!(entry.allows_cgroup("team_a/lidar"));
+        assert!(entry.allows_cgroup(ROOT_CGROUP));
+        assert!(!entry.allows_cgroup("camera2"));
+        assert!(!entry.allows_cgroup("team_b/lidar"));
+    }
+
+    #[test]
+    fn real_time_policies_need_a_max_priority() {
+        let entry = |max_priority| policy(max_priority).allow.remove(0);
+
+        assert!(entry(None).allows_policy(&SchedPolicy::other()));
+        assert!(!entry(None).allows_policy(&SchedPolicy::FIFO(1)));
+        assert!(entry(Some(50)).allows_policy(&SchedPolicy::RR(50)));
+        assert!(!entry(Some(50)).allows_policy(&SchedPolicy::FIFO(51)));
+    }
+
+    #[test]
+    fn entries_match_the_requester() {
+        let requester = requester();
+        let op = Operation::Release { pid: requester.pid };
+
+        let by_gid = AuthPolicy {
+            allow: vec![PolicyEntry { uid: None, gid: Some(requester.gid), ..policy(None).allow.remove(0) }],
+        };
+        assert!(by_gid.check(&requester, &op).is_ok());
+
+        let other = Requester { uid: requester.uid.wrapping_add(1), ..requester };
+        assert!(policy(None).check(&other, &op).is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use std::path::PathBuf;
+
+use hcbs_utils::prelude::*;
+
+/// Features of the running kernel, probed when the manager starts.
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    /// Per-CPU runtimes for a single cgroup.
+    pub multi_runtime: bool,
+    /// Deadline servers for SCHED_OTHER tasks (fair_server).
+    pub deadline_servers: bool,
+    /// The cpuset controller is available for the managed cgroups.
+    pub cpuset: bool,
+    pub min_period_us: u64,
+    pub max_period_us: u64,
+}
+
+impl Capabilities {
+    const MULTI_RUNTIME_FILE: &'static str = "cpu.rt_multi_runtime_us";
+    const FAIR_SERVER_DIR: &'static str = "/sys/kernel/debug/sched/fair_server";
+    const MIN_PERIOD_FILE: &'static str = "/proc/sys/kernel/sched_deadline_period_min_us";
+    const MAX_PERIOD_FILE: &'static str = "/proc/sys/kernel/sched_deadline_period_max_us";
+
+    /// Kernel defaults, used when the limits cannot be read.
+    const DEFAULT_MIN_PERIOD_US: u64 = 100;
+    const DEFAULT_MAX_PERIOD_US: u64 = 1 << 22;
+
+    pub fn probe() -> Self {
+        let root = PathBuf::from(cgroup_abs_path(ROOT_CGROUP).to_string());
+
+        let cpuset =
+            std::fs::read_to_string(root.join("cgroup.controllers"))
+            .is_ok_and(|controllers| controllers.split_whitespace().any(|c| c == "cpuset"));
+
+        let read_u64 = |path: &str| -> Option<u64> {
+            std::fs::read_to_string(path).ok()?.trim().parse().ok()
+        };
+
+        let capabilities = Self {
+            multi_runtime: root.join(Self::MULTI_RUNTIME_FILE).exists(),
+            deadline_servers: std::path::Path::new(Self::FAIR_SERVER_DIR).exists(),
+            cpuset,
+            min_period_us: read_u64(Self::MIN_PERIOD_FILE).unwrap_or(Self::DEFAULT_MIN_PERIOD_US),
+            max_period_us: read_u64(Self::MAX_PERIOD_FILE).unwrap_or(Self::DEFAULT_MAX_PERIOD_US),
+        };
+
+        info!("Kernel capabilities: {capabilities:?}");
+
+        capabilities
+    }
+
+    /// Checks that cgroups can be restricted to the given CPUs: this needs the
+    /// cpuset controller, and per-CPU runtimes unless every online CPU is
+    /// given, as the runtime would otherwise still be reserved on every CPU.
+    pub fn check_cpus(&self, cpus: &[CpuID]) -> anyhow::Result<()> {
+        if !self.cpuset {
+            anyhow::bail!("Cgroups cannot be restricted to CPUs {cpus:?}: the cpuset controller is not available.");
+        }
+
+        let online = CpuSet::all()?;
+        if let Some(cpu) = cpus.iter().find(|cpu| !online.iter().any(|online| online == *cpu)) {
+            anyhow::bail!("Cgroups cannot be restricted to CPUs {cpus:?}: CPU {cpu} is not online.");
+        }
+
+        if !self.multi_runtime && online.iter().any(|cpu| !cpus.contains(cpu)) {
+            anyhow::bail!("Cgroups cannot be restricted to CPUs {cpus:?}: the kernel does not support per-CPU runtimes (multi_runtime), \
+                so the runtime would still be reserved on every CPU.");
+        }
+
+        Ok(())
+    }
+
+    pub fn check_period(&self, period_us: u64) -> anyhow::Result<()> {
+        if period_us < self.min_period_us || period_us > self.max_period_us {
+            anyhow::bail!("Period {period_us}us is not supported by the kernel: it must be between {}us and {}us.",
+                self.min_period_us, self.max_period_us);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capabilities(cpuset: bool) -> Capabilities {
+        Capabilities {
+            multi_runtime: false,
+            deadline_servers: false,
+            cpuset,
+            min_period_us: Capabilities::DEFAULT_MIN_PERIOD_US,
+            max_period_us: Capabilities::DEFAULT_MAX_PERIOD_US,
+        }
+    }
+
+    #[test]
+    fn periods_must_be_within_the_kernel_limits() {
+        let capabilities = capabilities(true);
+
+        assert!(capabilities.check_period(10000).is_ok());
+        assert!(capabilities.check_period(Capabilities::DEFAULT_MIN_PERIOD_US - 1).is_err());
+        assert!(capabilities.check_period(Capabilities::DEFAULT_MAX_PERIOD_US + 1).is_err());
+    }
+
+    #[test]
+    fn cpus_need_the_cpuset_controller() {
+        assert!(capabilities(false).check_cpus(&[0]).is_err());
+    }
+}
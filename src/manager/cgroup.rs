@@ -1,115 +1,932 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use hcbs_utils::prelude::*;
 
+use super::{AnalysisTask, Quota, QuotaSubject, QuotaUsage, Requester, TransactionOp, analysis};
+use super::admission::{AdmissionPolicy, Allocation, UtilisationPolicy, Verdict};
+use crate::ops::{
+    assign_pid_to_cgroup,
+    cgroup_pids,
+    create_cgroup,
+    delete_cgroup,
+    kill_pid,
+    set_cgroup_cpus,
+    set_cgroup_period_us,
+    set_cgroup_runtime_us,
+    set_sched_policy,
+};
+
+/// Keeps the managed cgroups and their reservations. Admission uses the
+/// recorded reservations, so that it does not depend on the cgroups actually
+/// existing, e.g. in dry-run mode.
 #[derive(Debug)]
 pub struct CgroupManager {
-    cgroups: HashSet<String>,
+    cgroups: HashMap<String, CgroupData>,
+    quotas: Vec<Quota>,
+    admission: Box<dyn AdmissionPolicy>,
+    /// Last admission verdict of each cgroup name, with the requested
+    /// reservation.
+    verdicts: HashMap<String, (Reservation, Verdict)>,
+    /// Last admission test run without applying the request, for each user,
+    /// so that users don't see each other's tests as actual verdicts.
+    evaluations: HashMap<u32, (String, Reservation, Verdict)>,
 }
 
+#[derive(Debug, Clone)]
+pub struct CgroupData {
+    /// Requested reservation. For elastic cgroups, this is the guaranteed
+    /// minimum, used for admission.
+    pub reservation: Reservation,
+    pub elasticity: Option<Elasticity>,
+    /// Runtime currently set, which can exceed the requested one for elastic
+    /// cgroups.
+    pub granted_runtime_us: u64,
+    /// User and group which created the cgroup.
+    pub owner_uid: u32,
+    pub owner_gid: u32,
+    /// Real-time priorities allowed to the cgroup's processes, if restricted.
+    pub priorities: Option<PriorityRange>,
+    /// CPUs the cgroup is restricted to through its cpuset, if restricted.
+    pub cpus: Option<Vec<CpuID>>,
+    /// Tasks running in the cgroup, checked for schedulability on updates by
+    /// the `analysis` admission policy.
+    pub tasks: Vec<AnalysisTask>,
+}
+
+impl CgroupData {
+    pub fn priority_range(&self) -> PriorityRange {
+        self.priorities.unwrap_or(PriorityRange::FULL)
+    }
+
+    /// Relative priority matching the absolute one, if the cgroup has a
+    /// priority band containing it.
+    pub fn relative_priority(&self, priority: u8) -> Option<u8> {
+        self.priorities
+            .filter(|band| band.contains(priority))
+            .map(|band| band.max - priority)
+    }
+
+    /// Allocation of the cgroup, as seen by the admission policies.
+    pub fn allocation<'a>(&'a self, name: &'a str) -> Allocation<'a> {
+        Allocation {
+            name,
+            reservation: self.reservation,
+            cpus: self.cpus.as_deref(),
+            tasks: &self.tasks,
+        }
+    }
+
+    pub fn granted(&self) -> Reservation {
+        Reservation {
+            runtime_us: self.granted_runtime_us,
+            period_us: self.reservation.period_us,
+        }
+    }
+
+    /// Worst-case response time of each task inside the reservation.
+    pub fn response_times(&self) -> Vec<Option<u64>> {
+        analysis::response_times(&self.tasks, self.reservation)
+    }
+}
+
+/// What happens to the processes still running in a managed cgroup which is
+/// destroyed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EvictionPolicy {
+    /// Kill every process in the cgroup.
+    Kill,
+    /// Set every process to SCHED_OTHER and move it to the root cgroup.
+    Demote,
+    /// Restore managed processes to their original cgroup and scheduling
+    /// state, demoting the other ones.
+    Original,
+    /// Leave the cgroup and its processes in place, and stop managing it.
+    /// Only valid on exit, as the cgroup keeps its bandwidth.
+    Leave,
+}
+
+#[derive(Debug, Clone, Copy)]
 pub struct Reservation {
     pub runtime_us: u64,
     pub period_us: u64,
 }
 
-impl CgroupManager {
-    const MAX_RESOURCE: f64 = 0.95;
+impl Reservation {
+    pub fn bandwidth(&self) -> f64 {
+        self.runtime_us as f64 / self.period_us as f64
+    }
+}
+
+/// Elastic cgroups can use more than their requested runtime, up to their
+/// maximum, when there is spare bandwidth. The spare bandwidth is shared in
+/// proportion to the cgroups' weights.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Elasticity {
+    pub max_runtime_us: u64,
+    pub weight: u32,
+}
+
+/// Range of SCHED_FIFO/SCHED_RR priorities, bounds included. Ranges never
+/// reach the manager's own priority, so that tenants cannot starve it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriorityRange {
+    pub min: u8,
+    pub max: u8,
+}
+
+impl PriorityRange {
+    /// Priority of the manager itself, above every cgroup's range.
+    pub const MANAGER_PRIORITY: u8 = 99;
+    pub const FULL: Self = Self { min: 1, max: Self::MANAGER_PRIORITY - 1 };
+
+    pub fn new(min: u8, max: u8) -> anyhow::Result<Self> {
+        if min < Self::FULL.min || max > Self::FULL.max || min > max {
+            anyhow::bail!("Invalid priority range {min}-{max}: must be within {}.", Self::FULL);
+        }
+
+        Ok(Self { min, max })
+    }
+
+    pub fn contains(&self, priority: u8) -> bool {
+        (self.min..=self.max).contains(&priority)
+    }
 
-    pub fn new() -> Self {
+    pub fn clamp(&self, priority: u8) -> u8 {
+        priority.clamp(self.min, self.max)
+    }
+}
+
+impl std::fmt::Display for PriorityRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.min, self.max)
+    }
+}
+
+/// Scheduling policy requested for a process. Relative priorities count down
+/// from the top of the cgroup's priority band, where 0 is the highest one, so
+/// that applications need not know the priorities used by other tenants.
+#[derive(Debug, Clone)]
+pub enum PolicyRequest {
+    Absolute(SchedPolicy),
+    /// SCHED_FIFO or SCHED_RR, carrying the relative priority.
+    Relative(SchedPolicy),
+}
+
+impl Elasticity {
+    fn check(&self, request: &Reservation) -> anyhow::Result<()> {
+        if self.max_runtime_us < request.runtime_us || self.max_runtime_us > request.period_us {
+            anyhow::bail!("Invalid maximum runtime {}us: must be between the runtime and the period.", self.max_runtime_us);
+        }
+
+        if self.weight == 0 {
+            anyhow::bail!("Invalid elasticity weight: must be positive.");
+        }
+
+        Ok(())
+    }
+}
+
+impl CgroupManager {
+    pub fn new(admission: Box<dyn AdmissionPolicy>) -> Self {
         Self {
-            cgroups: HashSet::new(),
+            cgroups: HashMap::new(),
+            quotas: Vec::new(),
+            admission,
+            verdicts: HashMap::new(),
+            evaluations: HashMap::new(),
         }
     }
 
-    pub fn create_cgroup(&mut self, name: &str, request: Reservation) -> anyhow::Result<()> {
-        if self.cgroups.contains(name) {
+    pub fn admission_policy(&self) -> &dyn AdmissionPolicy {
+        self.admission.as_ref()
+    }
+
+    pub fn verdicts(&self) -> impl Iterator<Item = (&str, &Reservation, &Verdict)> {
+        self.verdicts.iter().map(|(name, (request, verdict))| (name.as_str(), request, verdict))
+    }
+
+    pub fn evaluation(&self, uid: u32) -> Option<(&str, &Reservation, &Verdict)> {
+        self.evaluations.get(&uid).map(|(name, request, verdict)| (name.as_str(), request, verdict))
+    }
+
+    pub fn set_quotas(&mut self, quotas: Vec<Quota>) {
+        self.quotas = quotas;
+    }
+
+    /// Usage of every subject with a quota, and of every user owning cgroups,
+    /// together with the subject's quota if any.
+    pub fn quota_usages(&self) -> Vec<(QuotaSubject, QuotaUsage, Option<&Quota>)> {
+        let mut subjects: Vec<_> =
+            self.quotas.iter()
+            .map(|quota| quota.subject)
+            .chain(self.cgroups.values().map(|data| QuotaSubject::User(data.owner_uid)))
+            .collect();
+        subjects.sort();
+        subjects.dedup();
+
+        subjects.into_iter()
+            .map(|subject| {
+                let quota = self.quotas.iter().find(|quota| quota.subject == subject);
+
+                (subject, self.usage(subject, None), quota)
+            })
+            .collect()
+    }
+
+    pub fn create_cgroup(&mut self, name: &str, request: Reservation, priorities: Option<PriorityRange>, elasticity: Option<Elasticity>, cpus: Option<Vec<CpuID>>, owner: &Requester) -> anyhow::Result<()> {
+        if self.cgroups.contains_key(name) {
             anyhow::bail!("Cgroup {} already exists.", cgroup_abs_path(name));
         }
 
-        if !self.run_admission_test(&request)? {
-            anyhow::bail!("Cgroup {} cannot be allocated: insufficient resources.", cgroup_abs_path(name));
+        check_cgroup_name(name)?;
+
+        if let Some(elasticity) = elasticity {
+            elasticity.check(&request)?;
         }
 
-        Cgroup::create(name, request)
-            .map_err(|err| anyhow::format_err!("Cgroup {} cannot be allocated: {err}", cgroup_abs_path(name)))?;
+        let allocation = Allocation { name, reservation: request, cpus: cpus.as_deref(), tasks: &[] };
+        let verdict = self.run_admission_test(&allocation, owner.uid, owner.gid, false);
+        self.verdicts.insert(name.to_owned(), (request, verdict.clone()));
+
+        if let Verdict::Rejected { reason, .. } = verdict {
+            anyhow::bail!("Cgroup {} cannot be allocated: {reason} {}",
+                cgroup_abs_path(name), self.counter_offer(&allocation, owner.uid, owner.gid, false));
+        }
 
-        self.cgroups.insert(name.to_owned());
+        self.reclaim();
+
+        if let Err(err) = Cgroup::create(name, request, cpus.as_deref()) {
+            self.rebalance();
+            anyhow::bail!("Cgroup {} cannot be allocated: {err}", cgroup_abs_path(name));
+        }
+
+        self.cgroups.insert(name.to_owned(), CgroupData {
+            reservation: request,
+            elasticity,
+            granted_runtime_us: request.runtime_us,
+            owner_uid: owner.uid,
+            owner_gid: owner.gid,
+            priorities,
+            cpus,
+            tasks: Vec::new(),
+        });
+
+        self.rebalance();
 
         Ok(())
     }
 
-    pub fn update_cgroup(&mut self, name: &str, request: Reservation) -> anyhow::Result<()> {
-        anyhow::bail!("Not implemented");
+    /// Updates the reservation of the cgroup, and its priority range,
+    /// elasticity and CPUs if given. A `Some(None)` range lifts the cgroup's
+    /// restriction, and a `Some(None)` elasticity makes the cgroup rigid.
+    /// Processes already running keep their priorities.
+    pub fn update_cgroup(&mut self, name: &str, request: Reservation, priorities: Option<Option<PriorityRange>>, elasticity: Option<Option<Elasticity>>, cpus: Option<Vec<CpuID>>) -> anyhow::Result<()> {
+        let Some(data) = self.cgroups.get(name)
+            else { anyhow::bail!("Cgroup {} does not exist.", cgroup_abs_path(name)); };
+
+        let elasticity = elasticity.unwrap_or(data.elasticity);
+        if let Some(elasticity) = elasticity {
+            elasticity.check(&request)?;
+        }
+
+        let (owner_uid, owner_gid) = (data.owner_uid, data.owner_gid);
+        let (new_cpus, tasks) = (cpus.clone().or_else(|| data.cpus.clone()), data.tasks.clone());
+
+        let allocation = Allocation { name, reservation: request, cpus: new_cpus.as_deref(), tasks: &tasks };
+        let verdict = self.run_admission_test(&allocation, owner_uid, owner_gid, true);
+        self.verdicts.insert(name.to_owned(), (request, verdict.clone()));
+
+        if let Verdict::Rejected { reason, .. } = verdict {
+            anyhow::bail!("Cgroup {} cannot be updated: {reason} {}",
+                cgroup_abs_path(name), self.counter_offer(&allocation, owner_uid, owner_gid, true));
+        }
+
+        self.reclaim();
+
+        let old = self.cgroups[name].granted();
+        if let Err(err) = Cgroup::update(name, old, request) {
+            self.rebalance();
+            anyhow::bail!("Cgroup {} cannot be updated: {err}", cgroup_abs_path(name));
+        }
+
+        if let Some(cpus) = &cpus {
+            if let Err(err) = set_cgroup_cpus(name, cpus) {
+                if let Err(rollback_err) = Cgroup::update(name, request, old) {
+                    error!("Cannot restore the reservation of cgroup \"{name}\": {rollback_err}");
+                }
+
+                self.rebalance();
+                anyhow::bail!("Cgroup {} cannot be updated: {err}", cgroup_abs_path(name));
+            }
+        }
+
+        if let Some(data) = self.cgroups.get_mut(name) {
+            data.reservation = request;
+            data.granted_runtime_us = request.runtime_us;
+            data.priorities = priorities.unwrap_or(data.priorities);
+            data.elasticity = elasticity;
+            data.cpus = cpus.or(data.cpus.take());
+        }
+
+        self.rebalance();
+
+        Ok(())
+    }
+
+    /// Checks that the state reached at the end of the transaction passes
+    /// admission, whatever the states in between. Cgroups created by the
+    /// transaction are owned by the requester.
+    pub fn check_transaction(&self, ops: &[TransactionOp], requester: &Requester) -> anyhow::Result<()> {
+        let mut state: HashMap<&str, (Allocation, u32, u32)> =
+            self.cgroups.iter()
+            .map(|(name, data)| (name.as_str(), (data.allocation(name), data.owner_uid, data.owner_gid)))
+            .collect();
+
+        let mut names = HashSet::new();
+        let mut owners = Vec::new();
+        for op in ops.iter() {
+            let name = op.name();
+            if !names.insert(name) {
+                anyhow::bail!("Cgroup {} appears more than once in the transaction.", cgroup_abs_path(name));
+            }
+
+            match op {
+                TransactionOp::Create { request, elasticity, cpus, tasks, .. } => {
+                    if state.contains_key(name) {
+                        anyhow::bail!("Cgroup {} already exists.", cgroup_abs_path(name));
+                    }
+
+                    if let Some(elasticity) = elasticity {
+                        elasticity.check(request)?;
+                    }
+
+                    if let Some(tasks) = tasks {
+                        analysis::check_schedulable(tasks, *request)
+                            .map_err(|err| anyhow::format_err!("Task set of cgroup {} is not schedulable: {err}", cgroup_abs_path(name)))?;
+                    }
+
+                    let allocation = Allocation {
+                        name,
+                        reservation: *request,
+                        cpus: cpus.as_deref(),
+                        tasks: tasks.as_deref().unwrap_or_default(),
+                    };
+                    state.insert(name, (allocation, requester.uid, requester.gid));
+                    owners.push((requester.uid, requester.gid));
+                },
+                TransactionOp::Update { request, elasticity, cpus, tasks, .. } => {
+                    let (Some(entry), Some(data)) = (state.get_mut(name), self.cgroups.get(name))
+                        else { anyhow::bail!("Cgroup {} does not exist.", cgroup_abs_path(name)); };
+
+                    if let Some(elasticity) = elasticity.unwrap_or(data.elasticity) {
+                        elasticity.check(request)?;
+                    }
+
+                    if let Some(tasks) = tasks {
+                        analysis::check_schedulable(tasks, *request)
+                            .map_err(|err| anyhow::format_err!("Task set of cgroup {} is not schedulable: {err}", cgroup_abs_path(name)))?;
+                    }
+
+                    entry.0.reservation = *request;
+                    if let Some(cpus) = cpus {
+                        entry.0.cpus = Some(cpus.as_slice());
+                    }
+                    if let Some(tasks) = tasks {
+                        entry.0.tasks = tasks.as_slice();
+                    }
+
+                    owners.push((data.owner_uid, data.owner_gid));
+                },
+                TransactionOp::Delete { .. } => {
+                    if state.remove(name).is_none() {
+                        anyhow::bail!("Cgroup {} does not exist.", cgroup_abs_path(name));
+                    }
+                },
+            }
+        }
+
+        let allocations: Vec<Allocation> = state.values().map(|(allocation, _, _)| *allocation).collect();
+
+        for op in ops.iter() {
+            let (TransactionOp::Create { name, .. } | TransactionOp::Update { name, .. }) = op
+                else { continue; };
+
+            let Some(request) = allocations.iter().find(|allocation| allocation.name == name.as_str())
+                else { continue; };
+
+            let others: Vec<_> =
+                allocations.iter()
+                .filter(|allocation| allocation.name != name.as_str())
+                .copied()
+                .collect();
+
+            if let Verdict::Rejected { reason, .. } = self.admission.admit(request, &others) {
+                anyhow::bail!("Cgroup {} cannot be allocated: {reason}", cgroup_abs_path(name));
+            }
+        }
+
+        // Only the quotas of the owners of created or updated cgroups are
+        // checked, as deletions can only lower the usage.
+        for quota in self.quotas.iter() {
+            let applies = |uid: u32, gid: u32| match quota.subject {
+                QuotaSubject::User(quota_uid) => uid == quota_uid,
+                QuotaSubject::Group(quota_gid) => gid == quota_gid,
+            };
+
+            if !owners.iter().any(|&(uid, gid)| applies(uid, gid)) {
+                continue;
+            }
+
+            let usage =
+                state.values()
+                .filter(|&&(_, uid, gid)| applies(uid, gid))
+                .fold(QuotaUsage::default(), |usage, (allocation, _, _)| QuotaUsage {
+                    bandwidth: usage.bandwidth + allocation.reservation.bandwidth(),
+                    cgroups: usage.cgroups + 1,
+                });
+
+            quota.check(usage, 0.0, 0)?;
+        }
+
+        Ok(())
+    }
+
+    /// Brings the cgroup back to the given state, creating it again if it was
+    /// destroyed. Used to roll back transactions, without admission.
+    pub fn restore_cgroup(&mut self, name: &str, data: CgroupData) -> anyhow::Result<()> {
+        self.reclaim();
+
+        let result = match self.cgroups.get(name) {
+            Some(current) => {
+                // The cpuset is only touched if changed, as it may not even be
+                // available when neither state restricts the CPUs.
+                let cpus = (current.cpus != data.cpus).then(|| data.cpus.as_deref().unwrap_or_default());
+
+                Cgroup::update(name, current.granted(), data.reservation)
+                .and_then(|_| cpus.map_or(Ok(()), |cpus| set_cgroup_cpus(name, cpus)))
+            },
+            None => Cgroup::create(name, data.reservation, data.cpus.as_deref()),
+        };
+
+        if let Err(err) = result {
+            self.rebalance();
+            anyhow::bail!("Cgroup {} cannot be restored: {err}", cgroup_abs_path(name));
+        }
+
+        self.cgroups.insert(name.to_owned(), CgroupData {
+            granted_runtime_us: data.reservation.runtime_us,
+            ..data
+        });
+
+        self.rebalance();
+
+        Ok(())
+    }
+
+    /// Replaces the task set of the cgroup, if schedulable inside its current
+    /// reservation. An empty task set disables the analysis.
+    pub fn set_tasks(&mut self, name: &str, tasks: Vec<AnalysisTask>) -> anyhow::Result<()> {
+        let Some(data) = self.cgroups.get_mut(name)
+            else { anyhow::bail!("Cgroup {} does not exist.", cgroup_abs_path(name)); };
+
+        analysis::check_schedulable(&tasks, data.reservation)
+            .map_err(|err| anyhow::format_err!("Task set of cgroup {} is not schedulable: {err}", cgroup_abs_path(name)))?;
+
+        data.tasks = tasks;
+
+        Ok(())
+    }
+
+    /// Whether the reservation would pass admission for the cgroup running
+    /// the given tasks, as an update if the cgroup exists, or else as a new
+    /// cgroup of the requester.
+    pub fn admits(&self, name: &str, request: &Reservation, tasks: &[AnalysisTask], requester: &Requester) -> bool {
+        match self.cgroups.get(name) {
+            Some(data) => self.run_admission_test(
+                &Allocation { reservation: *request, tasks, ..data.allocation(name) }, data.owner_uid, data.owner_gid, true),
+            None => self.run_admission_test(
+                &Allocation { name, reservation: *request, cpus: None, tasks }, requester.uid, requester.gid, false),
+        }.is_accepted()
+    }
+
+    /// Runs the admission test as for a creation, or for an update if the
+    /// cgroup exists, without changing anything but the requester's last
+    /// evaluation.
+    pub fn evaluate(&mut self, name: &str, request: Reservation, requester: &Requester) -> Verdict {
+        let verdict = match self.cgroups.get(name) {
+            Some(data) => self.run_admission_test(
+                &Allocation { reservation: request, ..data.allocation(name) }, data.owner_uid, data.owner_gid, true),
+            None => self.run_admission_test(
+                &Allocation { name, reservation: request, cpus: None, tasks: &[] }, requester.uid, requester.gid, false),
+        };
+
+        self.evaluations.insert(requester.uid, (name.to_owned(), request, verdict.clone()));
+
+        verdict
+    }
+
+    /// Largest runtime which would pass admission with the given period, for
+    /// the existing cgroup or for a new one owned by the requester and
+    /// restricted to the given CPUs. Zero if not even an empty reservation
+    /// would be admitted.
+    pub fn max_admissible_runtime(&self, name: &str, period_us: u64, cpus: Option<&[CpuID]>, requester: &Requester) -> u64 {
+        let reservation = Reservation { runtime_us: 0, period_us };
+
+        match self.cgroups.get(name) {
+            Some(data) => self.max_runtime(
+                &Allocation { reservation, ..data.allocation(name) }, data.owner_uid, data.owner_gid, true),
+            None => self.max_runtime(
+                &Allocation { name, reservation, cpus, tasks: &[] }, requester.uid, requester.gid, false),
+        }
+    }
+
+    /// Only the owner of a cgroup, or root, can change it or move processes
+    /// into it.
+    pub fn check_owner(&self, name: &str, requester: &Requester) -> anyhow::Result<()> {
+        let Some(data) = self.cgroups.get(name)
+            else { anyhow::bail!("Cgroup {} does not exist.", cgroup_abs_path(name)); };
+
+        if !requester.is_root() && requester.uid != data.owner_uid {
+            anyhow::bail!("Cgroup {} is owned by user {}: permission denied for user {}.",
+                cgroup_abs_path(name), data.owner_uid, requester.uid);
+        }
+
+        Ok(())
     }
 
     pub fn destroy_cgroup(&mut self, name: &str) -> anyhow::Result<()> {
-        self.cgroups.get(name)
-            .ok_or_else(|| anyhow::format_err!("Cgroup {} does not exist.", cgroup_abs_path(name)))
-            .and_then(|name| {
-                Cgroup::destroy(name)
-                .map_err(|err| anyhow::format_err!("Cgroup {} cannot be destroyed: {err}", cgroup_abs_path(name)))
-            })?;
+        if !self.cgroups.contains_key(name) {
+            anyhow::bail!("Cgroup {} does not exist.", cgroup_abs_path(name));
+        }
+
+        Cgroup::destroy(name)
+            .map_err(|err| anyhow::format_err!("Cgroup {} cannot be destroyed: {err}", cgroup_abs_path(name)))?;
 
         self.cgroups.remove(name);
+        self.verdicts.remove(name);
+
+        self.rebalance();
 
         Ok(())
     }
 
     pub fn is_managed_cgroup(&self, name: &str) -> bool {
-        self.cgroups.contains(name)
+        self.cgroups.contains_key(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&CgroupData> {
+        self.cgroups.get(name)
+    }
+
+    /// Translates the requested policy into an absolute one, for a process in
+    /// the given cgroup. Relative priorities need the cgroup to have a priority
+    /// band, and must fall within it.
+    pub fn resolve_policy(&self, cgroup: &str, request: &PolicyRequest) -> anyhow::Result<SchedPolicy> {
+        let relative = match request {
+            PolicyRequest::Absolute(policy) => return Ok(policy.clone()),
+            PolicyRequest::Relative(relative) => relative,
+        };
+
+        let Some(band) = self.cgroups.get(cgroup).and_then(|data| data.priorities)
+            else { anyhow::bail!("Relative priorities need a managed cgroup with a priority band, \"{cgroup}\" has none."); };
+
+        let (offset, with_priority): (u8, fn(u8) -> SchedPolicy) = match relative {
+            SchedPolicy::FIFO(offset) => (*offset, SchedPolicy::FIFO),
+            SchedPolicy::RR(offset) => (*offset, SchedPolicy::RR),
+            _ => anyhow::bail!("Only SCHED_FIFO/SCHED_RR accept relative priorities."),
+        };
+
+        if offset > band.max - band.min {
+            anyhow::bail!("Relative priority rel:{offset} is outside the priority band {band} of cgroup \"{cgroup}\"");
+        }
+
+        Ok(with_priority(band.max - offset))
+    }
+
+    pub fn managed_cgroups(&self) -> impl Iterator<Item = &str> {
+        self.cgroups.keys().map(|name| name.as_str())
     }
 
-    fn run_admission_test(&self, request: &Reservation) -> anyhow::Result<bool> {
-        let current_allocation =
+    pub fn cgroups(&self) -> impl Iterator<Item = (&str, &CgroupData)> {
+        self.cgroups.iter().map(|(name, data)| (name.as_str(), data))
+    }
+
+    /// Evicts the processes of the given cgroup. Managed processes must have
+    /// already been restored for the [`EvictionPolicy::Original`] policy, the
+    /// remaining ones are demoted. With [`EvictionPolicy::Leave`] the cgroup
+    /// is no longer managed, and is thus not destroyed on exit.
+    pub fn evict_cgroup(&mut self, name: &str, policy: EvictionPolicy) -> anyhow::Result<()> {
+        if !self.cgroups.contains_key(name) {
+            anyhow::bail!("Cgroup {} does not exist.", cgroup_abs_path(name));
+        }
+
+        if policy == EvictionPolicy::Leave {
+            self.cgroups.remove(name);
+            self.verdicts.remove(name);
+            return Ok(());
+        }
+
+        Cgroup::evict(name, policy)
+            .map_err(|err| anyhow::format_err!("Cgroup {} cannot be evicted: {err}", cgroup_abs_path(name)))
+    }
+
+    /// Shares the bandwidth left by the requested reservations among the
+    /// elastic cgroups, in proportion to their weights and up to their
+    /// maximum runtime, and applies the new runtimes. The admission policy
+    /// tells which cgroups share which capacity. Runtimes are all lowered
+    /// before any is raised, so that the capacities are never exceeded.
+    fn rebalance(&mut self) {
+        let allocations: Vec<Allocation> =
+            self.cgroups.iter()
+            .map(|(name, data)| data.allocation(name))
+            .collect();
+
+        // A cgroup in several groups only gets what fits in all of them.
+        let mut extra: HashMap<&str, f64> = HashMap::new();
+        for (members, spare) in self.admission.spare(&allocations) {
+            let elastic: Vec<(&str, f64, u32)> =
+                members.iter()
+                .filter_map(|name| {
+                    let data = &self.cgroups[*name];
+                    let elasticity = data.elasticity?;
+                    let headroom = (elasticity.max_runtime_us - data.reservation.runtime_us) as f64 / data.reservation.period_us as f64;
+
+                    Some((*name, headroom, elasticity.weight))
+                })
+                .collect();
+
+            for (name, grant) in share_spare(spare, &elastic) {
+                extra.entry(name)
+                    .and_modify(|extra| *extra = f64::min(*extra, grant))
+                    .or_insert(grant);
+            }
+        }
+
+        let mut changes: Vec<(String, Reservation, Reservation)> =
+            self.cgroups.iter()
+            .filter_map(|(name, data)| {
+                let max_runtime_us = data.elasticity?.max_runtime_us;
+                let extra = extra.get(name.as_str()).copied().unwrap_or(0.0);
+
+                let extra_us = (extra * data.reservation.period_us as f64).floor() as u64;
+                let runtime_us = u64::min(data.reservation.runtime_us + extra_us, max_runtime_us);
+
+                (runtime_us != data.granted_runtime_us).then(|| (
+                    name.to_owned(),
+                    data.granted(),
+                    Reservation { runtime_us, period_us: data.reservation.period_us },
+                ))
+            })
+            .collect();
+
+        changes.sort_by_key(|(_, old, new)| new.runtime_us > old.runtime_us);
+
+        self.apply_grants(changes);
+    }
+
+    /// Brings the elastic cgroups back to their requested runtime, so that
+    /// the spare bandwidth is free before changing the reservations.
+    fn reclaim(&mut self) {
+        let changes =
             self.cgroups.iter()
-            .map(|name| -> anyhow::Result<_> {
-                let runtime_us = get_cgroup_runtime_us(name)?;
-                let period_us = get_cgroup_period_us(name)?;
+            .filter(|(_, data)| data.granted_runtime_us > data.reservation.runtime_us)
+            .map(|(name, data)| (name.clone(), data.granted(), data.reservation))
+            .collect();
+
+        self.apply_grants(changes);
+    }
 
-                Ok(runtime_us as f64 / period_us as f64)
+    fn apply_grants(&mut self, changes: Vec<(String, Reservation, Reservation)>) {
+        for (name, old, new) in changes {
+            match Cgroup::update(&name, old, new) {
+                Ok(()) => {
+                    debug!("Cgroup \"{name}\" granted runtime {}us every {}us", new.runtime_us, new.period_us);
+
+                    if let Some(data) = self.cgroups.get_mut(&name) {
+                        data.granted_runtime_us = new.runtime_us;
+                    }
+                },
+                Err(err) => error!("Cannot rebalance cgroup \"{name}\": {err}"),
+            }
+        }
+    }
+
+    /// Resources owned by the given subject, not counting the excluded
+    /// cgroup.
+    fn usage(&self, subject: QuotaSubject, excluded: Option<&str>) -> QuotaUsage {
+        self.cgroups.iter()
+            .filter(|(name, _)| Some(name.as_str()) != excluded)
+            .filter(|(_, data)| match subject {
+                QuotaSubject::User(uid) => data.owner_uid == uid,
+                QuotaSubject::Group(gid) => data.owner_gid == gid,
+            })
+            .fold(QuotaUsage::default(), |usage, (_, data)| QuotaUsage {
+                bandwidth: usage.bandwidth + data.reservation.bandwidth(),
+                cgroups: usage.cgroups + 1,
             })
-            .try_fold(0.0, |acc, util| -> anyhow::Result<_> { Ok(acc + util?) })?;
+    }
+
+    /// Runs the admission policy on the requested reservation, not counting
+    /// the current reservation of the updated cgroup, then checks the quotas
+    /// of the owner and of its group.
+    fn run_admission_test(&self, request: &Allocation, owner_uid: u32, owner_gid: u32, updated: bool) -> Verdict {
+        let excluded = updated.then_some(request.name);
+
+        let others: Vec<_> =
+            self.cgroups.iter()
+            .filter(|(other, _)| Some(other.as_str()) != excluded)
+            .map(|(other, data)| data.allocation(other))
+            .collect();
+
+        let mut verdict = self.admission.admit(request, &others);
 
-        let new_allocation =
-            request.runtime_us as f64 / request.period_us as f64;
+        let new_cgroups = if updated { 0 } else { 1 };
+        for quota in self.quotas.iter() {
+            let applies = match quota.subject {
+                QuotaSubject::User(uid) => uid == owner_uid,
+                QuotaSubject::Group(gid) => gid == owner_gid,
+            };
 
-        Ok(new_allocation + current_allocation <= Self::MAX_RESOURCE)
+            if !applies {
+                continue;
+            }
+
+            let usage = self.usage(quota.subject, excluded);
+            if let Some(max_bandwidth) = quota.max_bandwidth {
+                verdict = verdict.limit(f64::max(0.0, max_bandwidth - usage.bandwidth));
+            }
+
+            if let Err(err) = quota.check(usage, request.reservation.bandwidth(), new_cgroups) {
+                verdict = verdict.reject(err.to_string(), None);
+            }
+        }
+
+        verdict
+    }
+
+    /// Largest admitted runtime with the period of the request, derived from
+    /// the headroom of the admission verdict.
+    fn max_runtime(&self, request: &Allocation, owner_uid: u32, owner_gid: u32, updated: bool) -> u64 {
+        let period_us = request.reservation.period_us;
+        let verdict = |runtime_us| {
+            self.run_admission_test(&Allocation { reservation: Reservation { runtime_us, period_us }, ..*request }, owner_uid, owner_gid, updated)
+        };
+
+        let full = verdict(period_us);
+        if full.is_accepted() {
+            return period_us;
+        }
+
+        let Some(headroom) = full.headroom()
+            else { return 0; };
+
+        let runtime_us = u64::min((headroom * period_us as f64).floor() as u64, period_us);
+
+        // Rounding can put the bandwidth just above the headroom.
+        (runtime_us.saturating_sub(2)..=runtime_us).rev()
+            .find(|&runtime_us| verdict(runtime_us).is_accepted())
+            .unwrap_or(0)
+    }
+
+    /// Describes the largest reservation which could be granted instead of a
+    /// rejected one, so that clients don't need to retry blindly.
+    fn counter_offer(&self, request: &Allocation, owner_uid: u32, owner_gid: u32, updated: bool) -> String {
+        let period_us = request.reservation.period_us;
+
+        match self.max_runtime(request, owner_uid, owner_gid, updated) {
+            0 => format!("No runtime can be granted with period {period_us}us."),
+            runtime_us => format!("At most {runtime_us}us every {period_us}us can be granted."),
+        }
     }
 }
 
 impl Default for CgroupManager {
     fn default() -> Self {
         Self {
-            cgroups: HashSet::with_capacity(0)
+            cgroups: HashMap::with_capacity(0),
+            quotas: Vec::with_capacity(0),
+            admission: Box::new(UtilisationPolicy),
+            verdicts: HashMap::with_capacity(0),
+            evaluations: HashMap::with_capacity(0),
         }
     }
 }
 
 impl Drop for CgroupManager {
     fn drop(&mut self) {
-        for name in self.cgroups.iter() {
-            if let Err(err) = Cgroup::force_destroy(name) {
+        let mut names: Vec<&String> = self.cgroups.keys().collect();
+        names.sort_by_key(|name| std::cmp::Reverse(cgroup_depth(name)));
+
+        for name in names {
+            if let Err(err) = Cgroup::destroy(name) {
                 error!("Error in destroying cgroup \"{name}\": {err}");
             }
         };
     }
 }
 
+/// Shares the spare bandwidth among the given `(name, headroom, weight)`
+/// cgroups, in proportion to their weights and up to their headroom. Cgroups
+/// reaching their headroom give back the rest of their share, which is shared
+/// again among the others.
+fn share_spare<'a>(mut spare: f64, cgroups: &[(&'a str, f64, u32)]) -> HashMap<&'a str, f64> {
+    let mut extra: HashMap<&str, f64> = cgroups.iter().map(|(name, _, _)| (*name, 0.0)).collect();
+
+    let mut open: Vec<&(&str, f64, u32)> = cgroups.iter().filter(|(_, headroom, _)| *headroom > 0.0).collect();
+    while spare > f64::EPSILON && !open.is_empty() {
+        let total_weight: f64 = open.iter().map(|(_, _, weight)| *weight as f64).sum();
+
+        let mut given = 0.0;
+        let mut still_open = Vec::with_capacity(open.len());
+        for cgroup in open.iter().copied() {
+            let &(name, headroom, weight) = cgroup;
+            let share = spare * weight as f64 / total_weight;
+            let grant = f64::min(share, headroom - extra[name]);
+
+            *extra.get_mut(name).unwrap() += grant;
+            given += grant;
+
+            if grant == share {
+                still_open.push(cgroup);
+            }
+        }
+
+        spare -= given;
+        if still_open.len() == open.len() {
+            break;
+        }
+
+        open = still_open;
+    }
+
+    extra
+}
+
+/// Nesting level of the cgroup, so that children can be destroyed before
+/// their parents.
+pub(super) fn cgroup_depth(name: &str) -> usize {
+    name.split('/').count()
+}
+
+/// Rejects the names whose folder would shadow a file of the cgroup folder,
+/// and relative path components.
+fn check_cgroup_name(name: &str) -> anyhow::Result<()> {
+    if name.split('/').any(|component| component == "." || component == "..") {
+        anyhow::bail!("Cgroup {} cannot be created: its name cannot contain \".\" or \"..\".", cgroup_abs_path(name));
+    }
+
+    let dir_name = crate::filesystem::cgroup_to_dir_name(name);
+    if crate::filesystem::RESERVED_CGROUP_NAMES.contains(&dir_name.as_str()) {
+        anyhow::bail!("Cgroup {} cannot be created: \"{dir_name}\" is a reserved name.", cgroup_abs_path(name));
+    }
+
+    Ok(())
+}
+
 struct Cgroup;
 
 impl Cgroup {
-    pub fn create(name: &str, reservation: Reservation) -> anyhow::Result<()> {
+    /// Creates the cgroup, restricted to the given CPUs if any, before its
+    /// runtime is reserved.
+    pub fn create(name: &str, reservation: Reservation, cpus: Option<&[CpuID]>) -> anyhow::Result<()> {
         create_cgroup(name)?;
 
-        set_cgroup_period_us(name, reservation.period_us)
+        cpus.map_or(Ok(()), |cpus| set_cgroup_cpus(name, cpus))
+            .and_then(|_| set_cgroup_period_us(name, reservation.period_us))
             .and_then(|_| set_cgroup_runtime_us(name, reservation.runtime_us))
             .map_err(|err| { if let Err(err) = delete_cgroup(name) { err } else { err } })?;
 
         Ok(())
     }
 
+    /// Changes the reservation of the cgroup, without ever exceeding the
+    /// bandwidth of both the old and new reservations: the runtime is first
+    /// lowered to the smallest of the two, then the period is changed, and
+    /// finally the new runtime is set. The old reservation is restored on
+    /// failure.
+    pub fn update(name: &str, old: Reservation, new: Reservation) -> anyhow::Result<()> {
+        let apply = |from: Reservation, to: Reservation| -> anyhow::Result<()> {
+            set_cgroup_runtime_us(name, u64::min(from.runtime_us, to.runtime_us))?;
+            set_cgroup_period_us(name, to.period_us)?;
+            set_cgroup_runtime_us(name, to.runtime_us)?;
+
+            Ok(())
+        };
+
+        apply(old, new)
+            .map_err(|err| {
+                if let Err(rollback_err) = apply(new, old) {
+                    error!("Cannot restore the reservation of cgroup \"{name}\": {rollback_err}");
+                }
+
+                err
+            })
+    }
+
     pub fn destroy(name: &str) -> anyhow::Result<()> {
-        if cgroup_num_procs(name)? > 0 {
+        // In dry-run mode the cgroup may have never been created.
+        if cgroup_exists(name) && cgroup_num_procs(name)? > 0 {
             error!("Cannot destroy cgroup \"{name}\": cgroup has active processes");
             anyhow::bail!("Cannot destroy cgroup \"{name}\": cgroup has active processes");
         }
@@ -121,13 +938,69 @@ impl Cgroup {
         Ok(())
     }
 
-    fn force_destroy(name: &str) -> anyhow::Result<()> {
-        for pid in cgroup_pids(name)? {
-            kill_pid(pid)?;
+    fn evict(name: &str, policy: EvictionPolicy) -> anyhow::Result<()> {
+        match policy {
+            EvictionPolicy::Kill => {
+                for pid in cgroup_pids(name)? {
+                    kill_pid(pid)?;
+                }
+
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            },
+            EvictionPolicy::Demote | EvictionPolicy::Original => {
+                for pid in cgroup_pids(name)? {
+                    set_sched_policy(pid, SchedPolicy::other())?;
+                    assign_pid_to_cgroup(ROOT_CGROUP, pid)?;
+                }
+            },
+            EvictionPolicy::Leave => (),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_shares(extra: &HashMap<&str, f64>, expected: &[(&str, f64)]) {
+        assert_eq!(extra.len(), expected.len());
+
+        for (name, share) in expected {
+            assert!((extra[name] - share).abs() < 1e-9, "cgroup {name} got {}, expected {share}", extra[name]);
         }
+    }
 
-        std::thread::sleep(std::time::Duration::from_millis(100));
+    #[test]
+    fn spare_is_shared_by_weight() {
+        let extra = share_spare(0.3, &[("a", 1.0, 1), ("b", 1.0, 2)]);
 
-        Self::destroy(name)
+        assert_shares(&extra, &[("a", 0.1), ("b", 0.2)]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn spare_beyond_headroom_goes_to_the_others() {
+        let extra = share_spare(0.3, &[("a", 0.05, 1), ("b", 1.0, 1), ("c", 0.0, 1)]);
+
+        assert_shares(&extra, &[("a", 0.05), ("b", 0.25), ("c", 0.0)]);
+    }
+
+    #[test]
+    fn spare_is_left_when_every_cgroup_is_full() {
+        let extra = share_spare(0.5, &[("a", 0.1, 1), ("b", 0.2, 3)]);
+
+        assert_shares(&extra, &[("a", 0.1), ("b", 0.2)]);
+        assert_shares(&share_spare(0.0, &[("a", 0.1, 1)]), &[("a", 0.0)]);
+    }
+
+    #[test]
+    fn reserved_names_are_rejected() {
+        assert!(check_cgroup_name("camera").is_ok());
+        assert!(check_cgroup_name("team/create").is_ok());
+        assert!(check_cgroup_name("create").is_err());
+        assert!(check_cgroup_name("transaction").is_err());
+        assert!(check_cgroup_name("team/./camera").is_err());
+        assert!(check_cgroup_name(".").is_err());
+    }
+}
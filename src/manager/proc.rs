@@ -2,32 +2,112 @@ use std::collections::HashMap;
 
 use hcbs_utils::prelude::*;
 
+use super::{PolicyRequest, PriorityAssignment, Timing};
+use crate::ops::{assign_pid_to_cgroup, cgroup_pids, get_pid_cgroup, get_sched_policy, set_sched_policy};
+
 #[derive(Debug)]
 pub struct ProcManager {
     procs: HashMap<Pid, ProcData>,
-    keep_on_exit: bool,
+    restore_policy: RestorePolicy,
+    ceiling_policy: CeilingPolicy,
+    priority_assignment: PriorityAssignment,
 }
 
 #[derive(Debug)]
 pub struct ProcData {
-    original_cgroup: String,
+    original: TaskState,
+    /// Timing parameters, if the priority is assigned automatically.
+    timing: Option<Timing>,
+}
+
+/// What happens to the managed processes when the manager exits or when their
+/// managed cgroup is deleted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum RestorePolicy {
+    /// Leave processes in their current cgroup and scheduling policy.
+    #[default]
+    Keep,
+    /// Restore processes to the state they had when first managed.
+    Restore,
+}
+
+/// What happens to real-time priorities outside of the priority range of the
+/// target cgroup.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum CeilingPolicy {
+    /// Reject the request.
+    #[default]
+    Reject,
+    /// Clamp the priority into the range.
+    Clamp,
+}
+
+/// Set of tasks affected by a cgroup or scheduling policy change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TaskScope {
+    /// Only the given task.
+    #[default]
+    Process,
+    /// Every thread of the given process.
+    Threads,
+    /// Every thread of the given process and of all its descendants.
+    Tree,
+}
+
+/// Cgroup and scheduling state of a process at a given instant, used to roll
+/// back operations which fail halfway and to restore processes once released.
+#[derive(Debug)]
+pub(super) struct TaskState {
+    cgroup: String,
+    policy: SchedPolicy,
+    nice: i32,
+    affinity: Vec<usize>,
 }
 
 impl ProcManager {
-    pub fn new(keep_on_exit: bool) -> Self {
-        Self { procs: HashMap::new(), keep_on_exit }
+    pub fn new(restore_policy: RestorePolicy, ceiling_policy: CeilingPolicy, priority_assignment: PriorityAssignment) -> Self {
+        Self { procs: HashMap::new(), restore_policy, ceiling_policy, priority_assignment }
     }
 
-    pub fn update_managed_processes<I>(&mut self, dead_procs: I)
+    pub fn update_managed_processes<I>(&mut self, cgroups: &super::CgroupManager, dead_procs: I)
         where I: Iterator<Item = Pid>,
     {
+        let mut timed_left = false;
         for proc in dead_procs {
-            self.procs.remove(&proc);
+            if let Some(data) = self.procs.remove(&proc) {
+                timed_left |= data.timing.is_some();
+            }
         }
+
+        if timed_left {
+            self.reassign_priorities(cgroups);
+        }
+    }
+
+    pub fn process_timing(&self, pid: Pid) -> Option<Timing> {
+        self.procs.get(&pid).and_then(|data| data.timing)
+    }
+
+    /// Registers the timing parameters of the process, or clears them, and
+    /// reassigns the priorities of the processes in managed cgroups. Once
+    /// cleared, the process is set to SCHED_OTHER.
+    pub fn set_process_timing(&mut self, cgroups: &super::CgroupManager, pid: Pid, timing: Option<Timing>) -> anyhow::Result<()> {
+        let data = self.get_managed_process(pid)?;
+        let was_timed = data.timing.is_some();
+        data.timing = timing;
+
+        if was_timed && timing.is_none() {
+            set_sched_policy(pid, SchedPolicy::other())?;
+        }
+
+        self.reassign_priorities(cgroups);
+
+        Ok(())
     }
 
-    pub fn assign_cgroup_to_process(&mut self, cgroups: &super::CgroupManager, pid: Pid, cgroup: &str) -> anyhow::Result<()> {
-        if !cgroup_exists(cgroup) {
+    pub fn assign_cgroup_to_process(&mut self, cgroups: &super::CgroupManager, pid: Pid, cgroup: &str, scope: TaskScope) -> anyhow::Result<()> {
+        // Managed cgroups are only recorded in dry-run mode.
+        if !cgroup_exists(cgroup) && !cgroups.is_managed_cgroup(cgroup) {
             anyhow::bail!("Cgroup \"{cgroup}\" does not exist");
         }
 
@@ -35,41 +115,290 @@ impl ProcManager {
             anyhow::bail!("Cgroup \"{cgroup}\" is not managed by this controller.");
         }
 
-        if !get_sched_policy(pid)?.is_other() {
-            anyhow::bail!("Only SCHED_OTHER processes are allowed to be moved between cgroups.");
+        let tasks = scope.tasks(pid)?;
+        for &task in tasks.iter() {
+            if !get_sched_policy(task)?.is_other() {
+                anyhow::bail!("Only SCHED_OTHER processes are allowed to be moved between cgroups.");
+            }
         }
 
-        self.get_managed_process(pid)?;
+        self.apply_to_tasks(&tasks, |task| Ok(assign_pid_to_cgroup(cgroup, task)?))?;
 
-        assign_pid_to_cgroup(cgroup, pid)?;
+        if tasks.iter().any(|&task| self.process_timing(task).is_some()) {
+            self.reassign_priorities(cgroups);
+        }
 
         Ok(())
     }
 
-    pub fn set_process_sched_policy(&mut self, cgroups: &super::CgroupManager, pid: Pid, policy: SchedPolicy) -> anyhow::Result<()> {
+    pub fn set_process_sched_policy(&mut self, cgroups: &super::CgroupManager, pid: Pid, request: PolicyRequest, scope: TaskScope) -> anyhow::Result<()> {
+        let tasks = scope.tasks(pid)?;
+        self.check_not_timed(&tasks)?;
+
+        // Tasks may be in different cgroups, each with its own priority range.
+        let mut policies = HashMap::with_capacity(tasks.len());
+        for &task in tasks.iter() {
+            let cgroup = get_pid_cgroup(task)?;
+            let policy = cgroups.resolve_policy(&cgroup, &request)?;
+
+            let task_policy = match policy {
+                SchedPolicy::OTHER { .. } => policy,
+                SchedPolicy::FIFO(_) | SchedPolicy::RR(_) => {
+                    if !cgroups.is_managed_cgroup(&cgroup) {
+                        anyhow::bail!("Processes can be set to SCHED_FIFO/SCHED_RR only if they are in a managed cgroup");
+                    }
+
+                    self.check_priority(cgroups, &cgroup, policy)?
+                },
+                _ => anyhow::bail!("unexpected"),
+            };
+
+            policies.insert(task, task_policy);
+        }
+
+        self.apply_to_tasks(&tasks, |task| Ok(set_sched_policy(task, policies[&task].clone())?))
+    }
+
+    pub fn place_process(&mut self, cgroups: &super::CgroupManager, pid: Pid, cgroup: &str, request: PolicyRequest) -> anyhow::Result<()> {
+        // Managed cgroups are only recorded in dry-run mode.
+        if !cgroup_exists(cgroup) && !cgroups.is_managed_cgroup(cgroup) {
+            anyhow::bail!("Cgroup \"{cgroup}\" does not exist");
+        }
+
+        if !cgroups.is_managed_cgroup(cgroup) && cgroup != ROOT_CGROUP {
+            anyhow::bail!("Cgroup \"{cgroup}\" is not managed by this controller.");
+        }
+
+        self.check_not_timed(&[pid])?;
+
+        let policy = cgroups.resolve_policy(cgroup, &request)?;
+
         match policy {
             SchedPolicy::OTHER { .. } => (),
             SchedPolicy::FIFO(_) | SchedPolicy::RR(_) => {
-                let cgroup = get_pid_cgroup(pid)?;
-
-                if !cgroups.is_managed_cgroup(&cgroup) {
-                    anyhow::bail!("Processes can be set to SCHED_FIFO/SCHED_RR only if they are in a managed cgroup");
+                if !cgroups.is_managed_cgroup(cgroup) {
+                    anyhow::bail!("Processes can be set to SCHED_FIFO/SCHED_RR only if they are placed in a managed cgroup");
                 }
             },
             _ => anyhow::bail!("unexpected"),
         }
 
+        let policy = self.check_priority(cgroups, cgroup, policy)?;
+
         self.get_managed_process(pid)?;
 
+        let previous = TaskState::capture(pid)?;
+
+        if let Err(err) = Self::apply_placement(pid, cgroup, policy) {
+            if let Err(rollback_err) = previous.restore(pid) {
+                error!("Couldn't roll back placement of PID {pid}: {rollback_err}");
+            }
+
+            return Err(err);
+        }
+
+        Ok(())
+    }
+
+    /// Restores the process to the state it had when first managed, and stops
+    /// managing it.
+    pub fn release_process(&mut self, cgroups: &super::CgroupManager, pid: Pid) -> anyhow::Result<()> {
+        let Some(data) = self.procs.remove(&pid)
+            else { anyhow::bail!("Process {pid} is not managed by this controller."); };
+
+        if let Err(err) = data.original.restore(pid) {
+            self.procs.insert(pid, data);
+            return Err(err);
+        }
+
+        if data.timing.is_some() {
+            self.reassign_priorities(cgroups);
+        }
+
+        Ok(())
+    }
+
+    pub fn restore_policy(&self) -> RestorePolicy {
+        self.restore_policy
+    }
+
+    pub fn is_managed_process(&self, pid: Pid) -> bool {
+        self.procs.contains_key(&pid)
+    }
+
+    /// Releases all the managed processes in the given cgroup. Processes which
+    /// were originally in the cgroup itself are moved to the root cgroup as
+    /// SCHED_OTHER. Processes which cannot be restored are logged and kept
+    /// managed, and the other ones are still released.
+    pub fn release_processes_in_cgroup(&mut self, cgroup: &str) -> anyhow::Result<()> {
+        let pids: Vec<_> =
+            self.procs.keys()
+            .copied()
+            .filter(|&pid| get_pid_cgroup(pid).is_ok_and(|pid_cgroup| pid_cgroup == cgroup))
+            .collect();
+
+        self.release_processes(&pids, cgroup)
+    }
+
+    /// Releases the given processes of the cgroup, as
+    /// [`Self::release_processes_in_cgroup`], wherever they are now. Processes
+    /// which are not managed are skipped.
+    pub fn release_processes(&mut self, pids: &[Pid], cgroup: &str) -> anyhow::Result<()> {
+        let mut failed = Vec::with_capacity(0);
+        for &pid in pids {
+            let Some(mut data) = self.procs.remove(&pid)
+                else { continue; };
+
+            if data.original.cgroup == cgroup {
+                data.original.cgroup = ROOT_CGROUP.to_owned();
+                data.original.policy = SchedPolicy::other();
+            }
+
+            if let Err(err) = data.original.restore(pid) {
+                error!("Couldn't restore PID {pid} to its original state: {err}");
+                self.procs.insert(pid, data);
+                failed.push(pid);
+            }
+        }
+
+        if !failed.is_empty() {
+            anyhow::bail!("Couldn't restore PIDs {failed:?} of cgroup \"{cgroup}\" to their original state.");
+        }
+
+        Ok(())
+    }
+
+    /// Assigns the priorities of the processes with timing parameters, in each
+    /// managed cgroup, within the cgroup's priority range.
+    fn reassign_priorities(&self, cgroups: &super::CgroupManager) {
+        let mut members: HashMap<String, Vec<(Pid, Timing)>> = HashMap::new();
+        for (&pid, data) in self.procs.iter() {
+            let Some(timing) = data.timing
+                else { continue; };
+
+            match get_pid_cgroup(pid) {
+                Ok(cgroup) if cgroups.is_managed_cgroup(&cgroup) =>
+                    members.entry(cgroup).or_default().push((pid, timing)),
+                _ => (),
+            }
+        }
+
+        for (cgroup, tasks) in members {
+            let Some(data) = cgroups.get(&cgroup)
+                else { continue; };
+
+            for (pid, priority) in self.priority_assignment.assign(&tasks, data.priority_range()) {
+                if matches!(get_sched_policy(pid), Ok(SchedPolicy::FIFO(current)) if current == priority) {
+                    continue;
+                }
+
+                match set_sched_policy(pid, SchedPolicy::FIFO(priority)) {
+                    Ok(()) => debug!("Assigned priority {priority} to PID {pid} in cgroup \"{cgroup}\""),
+                    Err(err) => warn!("Couldn't assign priority {priority} to PID {pid}: {err}"),
+                }
+            }
+        }
+    }
+
+    /// Processes with timing parameters have their priority assigned
+    /// automatically, and cannot be given a policy explicitly.
+    fn check_not_timed(&self, tasks: &[Pid]) -> anyhow::Result<()> {
+        if let Some(task) = tasks.iter().find(|&&task| self.process_timing(task).is_some()) {
+            anyhow::bail!("PID {task} has timing parameters and its priority is assigned automatically: clear them first.");
+        }
+
+        Ok(())
+    }
+
+    /// Policy a process in the given cgroup would be set to, once relative
+    /// priorities are resolved and checked against the cgroup's range.
+    pub fn effective_policy(&self, cgroups: &super::CgroupManager, cgroup: &str, request: &PolicyRequest) -> anyhow::Result<SchedPolicy> {
+        let policy = cgroups.resolve_policy(cgroup, request)?;
+
+        self.check_priority(cgroups, cgroup, policy)
+    }
+
+    /// Checks the real-time priority of the policy against the priority range
+    /// of the cgroup, clamping it into the range if so configured.
+    fn check_priority(&self, cgroups: &super::CgroupManager, cgroup: &str, policy: SchedPolicy) -> anyhow::Result<SchedPolicy> {
+        let Some(data) = cgroups.get(cgroup)
+            else { return Ok(policy); };
+
+        let range = data.priority_range();
+        let (priority, with_priority): (u8, fn(u8) -> SchedPolicy) = match policy {
+            SchedPolicy::FIFO(priority) => (priority, SchedPolicy::FIFO),
+            SchedPolicy::RR(priority) => (priority, SchedPolicy::RR),
+            _ => return Ok(policy),
+        };
+
+        if range.contains(priority) {
+            return Ok(policy);
+        }
+
+        match self.ceiling_policy {
+            CeilingPolicy::Reject =>
+                anyhow::bail!("Priority {priority} is outside the priority range {range} of cgroup \"{cgroup}\""),
+            CeilingPolicy::Clamp => {
+                let clamped = range.clamp(priority);
+                debug!("Clamped priority {priority} to {clamped} for cgroup \"{cgroup}\"");
+
+                Ok(with_priority(clamped))
+            },
+        }
+    }
+
+    fn apply_placement(pid: Pid, cgroup: &str, policy: SchedPolicy) -> anyhow::Result<()> {
+        if get_sched_policy(pid)?.is_other() {
+            assign_pid_to_cgroup(cgroup, pid)?;
+        } else if let Err(err) = assign_pid_to_cgroup(cgroup, pid) {
+            // The kernel refused to migrate the real-time process directly:
+            // demote it just for the time needed to migrate it.
+            debug!("Direct migration of PID {pid} to cgroup \"{cgroup}\" failed, demoting: {err}");
+
+            set_sched_policy(pid, SchedPolicy::other())?;
+            assign_pid_to_cgroup(cgroup, pid)?;
+        }
+
         set_sched_policy(pid, policy)?;
 
         Ok(())
     }
 
+    /// Applies the given operation to all the tasks, registering them as
+    /// managed. If the operation fails on any task, the already changed tasks
+    /// are rolled back to their previous cgroup and scheduling policy.
+    fn apply_to_tasks<F>(&mut self, tasks: &[Pid], mut op: F) -> anyhow::Result<()>
+        where F: FnMut(Pid) -> anyhow::Result<()>
+    {
+        let mut applied = Vec::with_capacity(tasks.len());
+
+        for &task in tasks.iter() {
+            let result =
+                self.get_managed_process(task)
+                .and_then(|_| TaskState::capture(task))
+                .and_then(|previous| {
+                    applied.push((task, previous));
+                    op(task)
+                });
+
+            if let Err(err) = result {
+                for (task, previous) in applied.into_iter().rev() {
+                    if let Err(rollback_err) = previous.restore(task) {
+                        error!("Couldn't roll back PID {task}: {rollback_err}");
+                    }
+                }
+
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
     fn get_managed_process(&mut self, pid: Pid) -> anyhow::Result<&mut ProcData> {
         if !self.procs.contains_key(&pid) {
-            let cgroup = get_pid_cgroup(pid)?;
-            self.procs.insert(pid, ProcData::new(cgroup));
+            let original = TaskState::capture(pid)?;
+            self.procs.insert(pid, ProcData::new(original));
         }
 
         Ok(self.procs.get_mut(&pid).unwrap())
@@ -80,33 +409,170 @@ impl Default for ProcManager {
     fn default() -> Self {
         Self {
             procs: HashMap::with_capacity(0),
-            keep_on_exit: false,
+            restore_policy: RestorePolicy::Keep,
+            ceiling_policy: CeilingPolicy::Reject,
+            priority_assignment: PriorityAssignment::RateMonotonic,
         }
     }
 }
 
 impl Drop for ProcManager {
     fn drop(&mut self) {
-        if !self.keep_on_exit {
+        if self.restore_policy == RestorePolicy::Keep {
             return;
         }
 
-        for (&pid, data) in self.procs.iter() {
-            if let Err(err) = set_sched_policy(pid, SchedPolicy::other()) {
-                error!("Couldn't set PID {pid} scheduling policy to SCHED_OTHER: {err}");
+        for (pid, data) in self.procs.drain() {
+            if let Err(err) = data.original.restore(pid) {
+                error!("Couldn't restore PID {pid} to its original state: {err}");
             }
+        }
+    }
+}
 
-            if let Err(err) = assign_pid_to_cgroup(&data.original_cgroup, pid) {
-                error!("Couldn't move PID {pid} to its original cgroup: {err}");
-            }
+impl ProcData {
+    fn new(original: TaskState) -> Self {
+        Self { original, timing: None }
+    }
+}
+
+/// Moves every process of the cgroup to the root cgroup as SCHED_OTHER, so that
+/// the cgroup can be destroyed, and returns their state to undo it. If a
+/// process cannot be moved, the ones already moved are moved back.
+pub(super) fn detach_processes(cgroup: &str) -> anyhow::Result<Vec<(Pid, TaskState)>> {
+    let mut detached = Vec::new();
+    for pid in cgroup_pids(cgroup)? {
+        let result = TaskState::capture(pid)
+            .and_then(|state| {
+                set_sched_policy(pid, SchedPolicy::other())?;
+                detached.push((pid, state));
+                assign_pid_to_cgroup(ROOT_CGROUP, pid)
+            });
+
+        if let Err(err) = result {
+            reattach_processes(detached);
+            anyhow::bail!("Couldn't detach PID {pid} from cgroup \"{cgroup}\": {err}");
         }
+    }
+
+    Ok(detached)
+}
 
-        self.procs.clear();
+/// Moves the detached processes back to their cgroup and scheduling policy.
+pub(super) fn reattach_processes(detached: Vec<(Pid, TaskState)>) {
+    for (pid, state) in detached.into_iter().rev() {
+        if let Err(err) = state.restore(pid) {
+            error!("Couldn't move PID {pid} back to cgroup \"{}\": {err}", state.cgroup);
+        }
     }
 }
 
-impl ProcData {
-    pub fn new(original_cgroup: String) -> Self {
-        Self { original_cgroup }
+impl TaskState {
+    fn capture(pid: Pid) -> anyhow::Result<Self> {
+        Ok(Self {
+            cgroup: get_pid_cgroup(pid)?,
+            policy: get_sched_policy(pid)?,
+            nice: get_nice(pid)?,
+            affinity: get_affinity(pid)?,
+        })
+    }
+
+    fn restore(&self, pid: Pid) -> anyhow::Result<()> {
+        set_sched_policy(pid, SchedPolicy::other())?;
+        assign_pid_to_cgroup(&self.cgroup, pid)?;
+        set_affinity(pid, &self.affinity)?;
+        set_sched_policy(pid, self.policy.clone())?;
+        set_nice(pid, self.nice)?;
+
+        Ok(())
+    }
+}
+
+impl TaskScope {
+    /// Lists the tasks in the scope of the given PID.
+    pub fn tasks(&self, pid: Pid) -> anyhow::Result<Vec<Pid>> {
+        match self {
+            TaskScope::Process => Ok(vec![pid]),
+            TaskScope::Threads => process_threads(pid),
+            TaskScope::Tree => {
+                let mut tasks = Vec::new();
+                let mut to_visit = vec![pid];
+
+                while let Some(pid) = to_visit.pop() {
+                    for tid in process_threads(pid)? {
+                        to_visit.extend(thread_children(pid, tid)?);
+                        tasks.push(tid);
+                    }
+                }
+
+                Ok(tasks)
+            },
+        }
     }
-}
\ No newline at end of file
+}
+
+fn process_threads(pid: Pid) -> anyhow::Result<Vec<Pid>> {
+    std::fs::read_dir(format!("/proc/{pid}/task"))?
+        .map(|entry| -> anyhow::Result<Pid> {
+            Ok(entry?.file_name().to_string_lossy().parse()?)
+        })
+        .collect()
+}
+
+fn thread_children(pid: Pid, tid: Pid) -> anyhow::Result<Vec<Pid>> {
+    std::fs::read_to_string(format!("/proc/{pid}/task/{tid}/children"))?
+        .split_whitespace()
+        .map(|child| -> anyhow::Result<Pid> { Ok(child.parse()?) })
+        .collect()
+}
+
+fn get_nice(pid: Pid) -> anyhow::Result<i32> {
+    // getpriority can legitimately return -1, errno must be checked instead.
+    let nice = unsafe {
+        *libc::__errno_location() = 0;
+        libc::getpriority(libc::PRIO_PROCESS, pid as libc::id_t)
+    };
+
+    match std::io::Error::last_os_error() {
+        err if err.raw_os_error().is_some_and(|errno| errno != 0) =>
+            Err(anyhow::format_err!("Couldn't get nice value of PID {pid}: {err}")),
+        _ => Ok(nice),
+    }
+}
+
+fn set_nice(pid: Pid, nice: i32) -> anyhow::Result<()> {
+    if crate::ops::dry_run(format_args!("set nice value of PID {pid} to {nice}")) { return Ok(()); }
+
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, pid as libc::id_t, nice) } < 0 {
+        anyhow::bail!("Couldn't set nice value of PID {pid}: {}", std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+fn get_affinity(pid: Pid) -> anyhow::Result<Vec<usize>> {
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+
+    if unsafe { libc::sched_getaffinity(pid as libc::pid_t, std::mem::size_of::<libc::cpu_set_t>(), &mut set) } < 0 {
+        anyhow::bail!("Couldn't get CPU affinity of PID {pid}: {}", std::io::Error::last_os_error());
+    }
+
+    Ok((0..libc::CPU_SETSIZE as usize)
+        .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) })
+        .collect())
+}
+
+fn set_affinity(pid: Pid, cpus: &[usize]) -> anyhow::Result<()> {
+    if crate::ops::dry_run(format_args!("set CPU affinity of PID {pid} to {cpus:?}")) { return Ok(()); }
+
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    for &cpu in cpus.iter() {
+        unsafe { libc::CPU_SET(cpu, &mut set) };
+    }
+
+    if unsafe { libc::sched_setaffinity(pid as libc::pid_t, std::mem::size_of::<libc::cpu_set_t>(), &set) } < 0 {
+        anyhow::bail!("Couldn't set CPU affinity of PID {pid}: {}", std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
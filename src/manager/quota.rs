@@ -0,0 +1,75 @@
+/// Limit on the reservations owned by a user or a group. Group quotas apply to
+/// the cgroups created by users whose primary group is the given one.
+#[derive(Debug, Clone)]
+pub struct Quota {
+    pub subject: QuotaSubject,
+    /// Maximum total bandwidth of the owned cgroups.
+    pub max_bandwidth: Option<f64>,
+    /// Maximum number of owned cgroups.
+    pub max_cgroups: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum QuotaSubject {
+    User(u32),
+    Group(u32),
+}
+
+/// Resources currently owned by a quota subject.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QuotaUsage {
+    pub bandwidth: f64,
+    pub cgroups: usize,
+}
+
+impl Quota {
+    /// Checks whether the subject's usage, together with the requested
+    /// bandwidth and the given number of new cgroups, fits in the quota.
+    pub fn check(&self, usage: QuotaUsage, bandwidth: f64, new_cgroups: usize) -> anyhow::Result<()> {
+        if let Some(max_bandwidth) = self.max_bandwidth {
+            if usage.bandwidth + bandwidth > max_bandwidth {
+                anyhow::bail!("{} quota exceeded: requested bandwidth {:.3}, {:.3} available out of {:.3}.",
+                    self.subject, bandwidth, f64::max(0.0, max_bandwidth - usage.bandwidth), max_bandwidth);
+            }
+        }
+
+        if let Some(max_cgroups) = self.max_cgroups {
+            if usage.cgroups + new_cgroups > max_cgroups {
+                anyhow::bail!("{} quota exceeded: at most {max_cgroups} cgroups allowed.", self.subject);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for QuotaSubject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuotaSubject::User(uid) => write!(f, "user {uid}"),
+            QuotaSubject::Group(gid) => write!(f, "group {gid}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quotas_bound_bandwidth_and_cgroups() {
+        let quota = Quota { subject: QuotaSubject::User(1000), max_bandwidth: Some(0.5), max_cgroups: Some(2) };
+        let usage = QuotaUsage { bandwidth: 0.3, cgroups: 1 };
+
+        assert!(quota.check(usage, 0.1, 1).is_ok());
+        assert!(quota.check(usage, 0.25, 0).is_err());
+        assert!(quota.check(usage, 0.1, 2).is_err());
+    }
+
+    #[test]
+    fn missing_limits_are_unbounded() {
+        let quota = Quota { subject: QuotaSubject::Group(100), max_bandwidth: None, max_cgroups: None };
+
+        assert!(quota.check(QuotaUsage { bandwidth: 10.0, cgroups: 100 }, 1.0, 1).is_ok());
+    }
+}
@@ -0,0 +1,130 @@
+use hcbs_utils::prelude::*;
+
+use crate::ProcessStats;
+
+/// Keeps the rules used to automatically place newly spawned processes into
+/// managed cgroups. Rules are evaluated in insertion order and the first
+/// matching rule is applied.
+#[derive(Debug)]
+pub struct RulesManager {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub name: String,
+    pub matchers: Vec<RuleMatch>,
+    pub cgroup: String,
+    pub policy: SchedPolicy,
+}
+
+#[derive(Debug, Clone)]
+pub enum RuleMatch {
+    Exe(std::path::PathBuf),
+    Comm(String),
+    Cmdline(regex::Regex),
+    Uid(u32),
+    /// Matches on the parent process' command name.
+    Parent(String),
+}
+
+impl RulesManager {
+    pub fn new() -> Self {
+        Self {
+            rules: Vec::new(),
+        }
+    }
+
+    pub fn add_rule(&mut self, rule: Rule) {
+        match self.rules.iter_mut().find(|other| other.name == rule.name) {
+            Some(other) => { *other = rule; },
+            None => { self.rules.push(rule); },
+        }
+    }
+
+    pub fn remove_rule(&mut self, name: &str) -> anyhow::Result<()> {
+        let Some(index) = self.rules.iter().position(|rule| rule.name == name)
+            else { anyhow::bail!("Rule \"{name}\" does not exist."); };
+
+        self.rules.remove(index);
+
+        Ok(())
+    }
+
+    pub fn rules(&self) -> impl Iterator<Item = &Rule> {
+        self.rules.iter()
+    }
+
+    pub fn matching_rule(&self, stats: &ProcessStats, parent: Option<&ProcessStats>) -> Option<&Rule> {
+        self.rules.iter()
+            .find(|rule| rule.matches(stats, parent))
+    }
+}
+
+impl Default for RulesManager {
+    fn default() -> Self {
+        Self {
+            rules: Vec::with_capacity(0),
+        }
+    }
+}
+
+impl Rule {
+    pub fn matches(&self, stats: &ProcessStats, parent: Option<&ProcessStats>) -> bool {
+        self.matchers.iter()
+            .all(|matcher| matcher.matches(stats, parent))
+    }
+}
+
+impl RuleMatch {
+    pub fn matches(&self, stats: &ProcessStats, parent: Option<&ProcessStats>) -> bool {
+        match self {
+            RuleMatch::Exe(exe) => stats.exe.as_ref() == Some(exe),
+            RuleMatch::Comm(comm) => &stats.comm == comm,
+            RuleMatch::Cmdline(regex) => regex.is_match(&stats.cmdline),
+            RuleMatch::Uid(uid) => *stats.uid == *uid,
+            RuleMatch::Parent(comm) => parent.is_some_and(|parent| &parent.comm == comm),
+        }
+    }
+}
+
+impl std::fmt::Display for RuleMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleMatch::Exe(exe) => write!(f, "exe={}", exe.display()),
+            RuleMatch::Comm(comm) => write!(f, "comm={comm}"),
+            RuleMatch::Cmdline(regex) => write!(f, "cmdline={}", regex.as_str()),
+            RuleMatch::Uid(uid) => write!(f, "uid={uid}"),
+            RuleMatch::Parent(comm) => write!(f, "parent={comm}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(name: &str, cgroup: &str) -> Rule {
+        Rule {
+            name: name.to_owned(),
+            matchers: vec![RuleMatch::Comm(name.to_owned())],
+            cgroup: cgroup.to_owned(),
+            policy: SchedPolicy::FIFO(50),
+        }
+    }
+
+    #[test]
+    fn rules_are_replaced_in_place() {
+        let mut rules = RulesManager::new();
+        rules.add_rule(rule("camera", "a"));
+        rules.add_rule(rule("lidar", "b"));
+        rules.add_rule(rule("camera", "c"));
+
+        let cgroups: Vec<_> = rules.rules().map(|rule| (rule.name.as_str(), rule.cgroup.as_str())).collect();
+        assert_eq!(cgroups, vec![("camera", "c"), ("lidar", "b")]);
+
+        assert!(rules.remove_rule("camera").is_ok());
+        assert!(rules.remove_rule("camera").is_err());
+        assert_eq!(rules.rules().count(), 1);
+    }
+}
@@ -0,0 +1,85 @@
+use hcbs_utils::prelude::*;
+
+use super::PriorityRange;
+
+/// Timing parameters of a periodic process, registered to have its priority
+/// assigned automatically.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    pub period_us: u64,
+    /// Relative deadline, the period if not given.
+    pub deadline_us: Option<u64>,
+    /// Worst-case execution time.
+    pub wcet_us: Option<u64>,
+}
+
+/// Order used to assign priorities to the processes with timing parameters
+/// in the same managed cgroup: shorter periods, or deadlines, get higher
+/// priorities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum PriorityAssignment {
+    #[default]
+    RateMonotonic,
+    DeadlineMonotonic,
+}
+
+impl Timing {
+    pub fn new(period_us: u64, deadline_us: Option<u64>, wcet_us: Option<u64>) -> anyhow::Result<Self> {
+        if period_us == 0 {
+            anyhow::bail!("Invalid timing: the period must be positive.");
+        }
+
+        if deadline_us.is_some_and(|deadline_us| deadline_us == 0 || deadline_us > period_us) {
+            anyhow::bail!("Invalid timing: the deadline must be positive and at most the period.");
+        }
+
+        if wcet_us.is_some_and(|wcet_us| wcet_us == 0 || wcet_us > deadline_us.unwrap_or(period_us)) {
+            anyhow::bail!("Invalid timing: the WCET must be positive and at most the deadline.");
+        }
+
+        Ok(Self { period_us, deadline_us, wcet_us })
+    }
+
+    pub fn deadline_us(&self) -> u64 {
+        self.deadline_us.unwrap_or(self.period_us)
+    }
+}
+
+impl PriorityAssignment {
+    /// Assigns a priority to each task, from the top of the range downwards.
+    /// Tasks with the same period, or deadline, share the same priority. If
+    /// there are more distinct levels than priorities in the range, the
+    /// lowest levels share the range's minimum priority.
+    pub fn assign(&self, tasks: &[(Pid, Timing)], range: PriorityRange) -> Vec<(Pid, u8)> {
+        let key = |timing: &Timing| match self {
+            PriorityAssignment::RateMonotonic => timing.period_us,
+            PriorityAssignment::DeadlineMonotonic => timing.deadline_us(),
+        };
+
+        let mut tasks = tasks.to_vec();
+        tasks.sort_by_key(|(pid, timing)| (key(timing), *pid));
+
+        let mut priorities = Vec::with_capacity(tasks.len());
+        let mut priority = range.max;
+        let mut last_key = None;
+        let mut saturated = false;
+
+        for (pid, timing) in tasks {
+            let key = key(&timing);
+
+            if last_key.is_some_and(|last_key| last_key != key) {
+                saturated |= priority == range.min;
+                priority = u8::max(priority - 1, range.min);
+            }
+
+            last_key = Some(key);
+            priorities.push((pid, priority));
+        }
+
+        if saturated {
+            warn!("Not enough priorities in range {range}: the lowest priority levels are merged.");
+        }
+
+        priorities
+    }
+}
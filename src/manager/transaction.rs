@@ -0,0 +1,185 @@
+use hcbs_utils::prelude::*;
+
+use super::{AnalysisTask, CgroupData, Elasticity, EvictionPolicy, PriorityRange, Reservation};
+use super::cgroup::cgroup_depth;
+use super::proc::TaskState;
+
+/// Single step of a transaction. Each cgroup can appear in at most one step.
+#[derive(Debug, Clone)]
+pub enum TransactionOp {
+    Create {
+        name: String,
+        request: Reservation,
+        priorities: Option<PriorityRange>,
+        elasticity: Option<Elasticity>,
+        /// Restricts the cgroup to the given CPUs.
+        cpus: Option<Vec<CpuID>>,
+        /// Replaces the task set, if given.
+        tasks: Option<Vec<AnalysisTask>>,
+    },
+    Update {
+        name: String,
+        request: Reservation,
+        /// Replaces the priority range, if given. `Some(None)` clears it.
+        priorities: Option<Option<PriorityRange>>,
+        /// Replaces the elasticity, if given. `Some(None)` clears it.
+        elasticity: Option<Option<Elasticity>>,
+        /// Restricts the cgroup to the given CPUs.
+        cpus: Option<Vec<CpuID>>,
+        /// Replaces the task set, if given.
+        tasks: Option<Vec<AnalysisTask>>,
+    },
+    Delete {
+        name: String,
+        eviction: Option<EvictionPolicy>,
+    },
+}
+
+/// Named set of cgroups, e.g. for the startup, nominal and degraded modes of
+/// the system. Switching to a mode creates or updates its cgroups, and deletes
+/// the cgroups of the other modes.
+#[derive(Debug, Clone)]
+pub struct Mode {
+    pub name: String,
+    pub cgroups: Vec<ModeCgroup>,
+    /// Eviction policy of the deleted cgroups which still have processes.
+    pub eviction: Option<EvictionPolicy>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ModeCgroup {
+    pub name: String,
+    pub reservation: Reservation,
+    pub tasks: Vec<AnalysisTask>,
+}
+
+/// Applied step, with what is needed to undo it. The processes of deleted
+/// cgroups are only detached, and evicted once the whole transaction is
+/// applied.
+#[derive(Debug)]
+pub(super) enum Applied {
+    Created(String),
+    Changed(String, CgroupData),
+    Deleted(String, CgroupData, Vec<(Pid, TaskState)>, Option<EvictionPolicy>),
+}
+
+impl TransactionOp {
+    pub fn name(&self) -> &str {
+        match self {
+            TransactionOp::Create { name, .. } => name,
+            TransactionOp::Update { name, .. } => name,
+            TransactionOp::Delete { name, .. } => name,
+        }
+    }
+
+    /// Application order of the steps: shrinking updates and deletions free
+    /// bandwidth before creations and growing updates use it, so that every
+    /// intermediate state needs no more than the final one.
+    pub(super) fn phase(&self, current: Option<&CgroupData>) -> u8 {
+        match self {
+            TransactionOp::Update { request, .. }
+                if current.is_some_and(|data| request.bandwidth() <= data.reservation.bandwidth()) => 0,
+            TransactionOp::Delete { .. } => 1,
+            TransactionOp::Create { .. } => 2,
+            TransactionOp::Update { .. } => 3,
+        }
+    }
+
+    /// Order of the steps within a phase: parents are created before their
+    /// nested cgroups, and deleted after them.
+    pub(super) fn nesting_order(&self) -> isize {
+        let depth = cgroup_depth(self.name()) as isize;
+
+        match self {
+            TransactionOp::Delete { .. } => -depth,
+            _ => depth,
+        }
+    }
+}
+
+impl std::fmt::Display for TransactionOp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransactionOp::Create { name, .. } => write!(f, "create \"{name}\""),
+            TransactionOp::Update { name, .. } => write!(f, "update \"{name}\""),
+            TransactionOp::Delete { name, .. } => write!(f, "delete \"{name}\""),
+        }
+    }
+}
+
+impl Mode {
+    pub fn contains(&self, cgroup: &str) -> bool {
+        self.cgroups.iter().any(|mode_cgroup| mode_cgroup.name == cgroup)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(name: &str, runtime_us: u64) -> TransactionOp {
+        TransactionOp::Update {
+            name: name.to_owned(),
+            request: Reservation { runtime_us, period_us: 10000 },
+            priorities: None,
+            elasticity: None,
+            cpus: None,
+            tasks: None,
+        }
+    }
+
+    fn current(runtime_us: u64) -> CgroupData {
+        CgroupData {
+            reservation: Reservation { runtime_us, period_us: 10000 },
+            elasticity: None,
+            granted_runtime_us: runtime_us,
+            owner_uid: 0,
+            owner_gid: 0,
+            priorities: None,
+            cpus: None,
+            tasks: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn steps_free_bandwidth_before_using_it() {
+        let data = current(2000);
+
+        let mut ops = vec![
+            update("grow", 3000),
+            TransactionOp::Create {
+                name: "create".to_owned(),
+                request: Reservation { runtime_us: 1000, period_us: 10000 },
+                priorities: None,
+                elasticity: None,
+                cpus: None,
+                tasks: None,
+            },
+            TransactionOp::Delete { name: "delete".to_owned(), eviction: None },
+            update("shrink", 1000),
+        ];
+        ops.sort_by_key(|op| op.phase(Some(&data)));
+
+        let names: Vec<_> = ops.iter().map(TransactionOp::name).collect();
+        assert_eq!(names, vec!["shrink", "delete", "create", "grow"]);
+    }
+
+    #[test]
+    fn nested_cgroups_are_deleted_first() {
+        let delete = |name: &str| TransactionOp::Delete { name: name.to_owned(), eviction: None };
+
+        let mut ops = vec![delete("a"), delete("a/b"), delete("c"), delete("a/b/c")];
+        ops.sort_by_key(|op| (op.phase(None), op.nesting_order()));
+
+        let names: Vec<_> = ops.iter().map(TransactionOp::name).collect();
+        assert_eq!(names, vec!["a/b/c", "a/b", "a", "c"]);
+    }
+
+    #[test]
+    fn unchanged_bandwidth_is_applied_first() {
+        let data = current(2000);
+
+        assert_eq!(update("same", 2000).phase(Some(&data)), 0);
+        assert_eq!(update("missing", 1000).phase(None), 3);
+    }
+}
@@ -0,0 +1,207 @@
+//! Wrappers around the operations which change the system state. In dry-run
+//! mode the operations are printed instead of being applied, while reads are
+//! still performed on the real system. The cgroup and scheduling policy of
+//! processes are the exception: their simulated changes are tracked, so that
+//! later requests see them, e.g. a process moved into a managed cgroup can
+//! then be set to SCHED_FIFO.
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use hcbs_utils::prelude::*;
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Process changes simulated in dry-run mode.
+static DRY_RUN_PROCS: LazyLock<Mutex<DryRunProcs>> = LazyLock::new(Default::default);
+
+#[derive(Debug, Default)]
+struct DryRunProcs {
+    cgroups: HashMap<Pid, String>,
+    policies: HashMap<Pid, SchedPolicy>,
+}
+
+pub fn set_dry_run(enabled: bool) {
+    DRY_RUN.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+/// Prints the given operation in dry-run mode. Returns whether the operation
+/// must be skipped.
+pub(crate) fn dry_run(op: std::fmt::Arguments) -> bool {
+    if !is_dry_run() {
+        return false;
+    }
+
+    println!("[dry-run] {op}");
+    true
+}
+
+pub fn mount_cgroup_fs() -> anyhow::Result<()> {
+    if dry_run(format_args!("mount cgroup filesystem")) { return Ok(()); }
+
+    Ok(hcbs_utils::prelude::mount_cgroup_fs()?)
+}
+
+pub fn create_cgroup(name: &str) -> anyhow::Result<()> {
+    if dry_run(format_args!("create cgroup {}", cgroup_abs_path(name))) { return Ok(()); }
+
+    Ok(hcbs_utils::prelude::create_cgroup(name)?)
+}
+
+pub fn delete_cgroup(name: &str) -> anyhow::Result<()> {
+    if dry_run(format_args!("delete cgroup {}", cgroup_abs_path(name))) { return Ok(()); }
+
+    Ok(hcbs_utils::prelude::delete_cgroup(name)?)
+}
+
+pub fn set_cgroup_runtime_us(name: &str, runtime_us: u64) -> anyhow::Result<()> {
+    if dry_run(format_args!("set runtime of cgroup {} to {runtime_us}us", cgroup_abs_path(name))) { return Ok(()); }
+
+    Ok(hcbs_utils::prelude::set_cgroup_runtime_us(name, runtime_us)?)
+}
+
+pub fn set_cgroup_period_us(name: &str, period_us: u64) -> anyhow::Result<()> {
+    if dry_run(format_args!("set period of cgroup {} to {period_us}us", cgroup_abs_path(name))) { return Ok(()); }
+
+    Ok(hcbs_utils::prelude::set_cgroup_period_us(name, period_us)?)
+}
+
+/// Pids of the given cgroup. In dry-run mode, cgroups which were never created
+/// only have the processes simulated to be moved into them.
+pub fn cgroup_pids(name: &str) -> anyhow::Result<Vec<Pid>> {
+    let mut pids: Vec<Pid> =
+        if is_dry_run() && !cgroup_exists(name) { Vec::with_capacity(0) }
+        else { hcbs_utils::prelude::cgroup_pids(name)?.into_iter().collect() };
+
+    if is_dry_run() {
+        let procs = dry_run_procs();
+
+        pids.retain(|pid| procs.cgroups.get(pid).is_none_or(|cgroup| cgroup == name));
+        pids.extend(
+            procs.cgroups.iter()
+            .filter(|&(pid, cgroup)| cgroup == name && !pids.contains(pid))
+            .map(|(&pid, _)| pid)
+            .collect::<Vec<_>>());
+    }
+
+    Ok(pids)
+}
+
+/// Cgroup of the given process, as simulated in dry-run mode.
+pub fn get_pid_cgroup(pid: Pid) -> anyhow::Result<String> {
+    if let Some(cgroup) = is_dry_run().then(|| dry_run_procs().cgroups.get(&pid).cloned()).flatten() {
+        return Ok(cgroup);
+    }
+
+    Ok(hcbs_utils::prelude::get_pid_cgroup(pid)?)
+}
+
+/// Scheduling policy of the given process, as simulated in dry-run mode.
+pub fn get_sched_policy(pid: Pid) -> anyhow::Result<SchedPolicy> {
+    if let Some(policy) = is_dry_run().then(|| dry_run_procs().policies.get(&pid).cloned()).flatten() {
+        return Ok(policy);
+    }
+
+    Ok(hcbs_utils::prelude::get_sched_policy(pid)?)
+}
+
+/// CPUs the reservation of the given cgroup runs on: its effective cpuset, or
+/// every online CPU without the cpuset controller. In dry-run mode, cgroups
+/// which were never created run on every CPU.
+pub fn cgroup_cpus(name: &str) -> anyhow::Result<Vec<CpuID>> {
+    let path = format!("{}/cpuset.cpus.effective", cgroup_abs_path(name));
+
+    if (is_dry_run() && !cgroup_exists(name)) || !std::path::Path::new(&path).exists() {
+        return Ok(CpuSet::all()?.iter().copied().collect());
+    }
+
+    crate::tuning::parse_cpu_list(&std::fs::read_to_string(&path)?)
+}
+
+/// Restricts the cgroup to the given CPUs, enabling the cpuset controller on
+/// its ancestors. No CPUs lets the cgroup use every CPU of its parent.
+pub fn set_cgroup_cpus(name: &str, cpus: &[CpuID]) -> anyhow::Result<()> {
+    let list = cpus.iter().map(|cpu| cpu.to_string()).collect::<Vec<_>>().join(",");
+
+    if dry_run(format_args!("set CPUs of cgroup {} to \"{list}\"", cgroup_abs_path(name))) { return Ok(()); }
+
+    let ancestors =
+        std::iter::once(ROOT_CGROUP)
+        .chain(name.match_indices('/').map(|(index, _)| &name[..index]));
+    for ancestor in ancestors {
+        write_file(format!("{}/cgroup.subtree_control", cgroup_abs_path(ancestor)), "+cpuset")?;
+    }
+
+    write_file(format!("{}/cpuset.cpus", cgroup_abs_path(name)), &list)
+}
+
+pub fn assign_pid_to_cgroup(name: &str, pid: Pid) -> anyhow::Result<()> {
+    if dry_run(format_args!("move PID {pid} to cgroup {}", cgroup_abs_path(name))) {
+        dry_run_procs().cgroups.insert(pid, name.to_owned());
+        return Ok(());
+    }
+
+    Ok(hcbs_utils::prelude::assign_pid_to_cgroup(name, pid)?)
+}
+
+pub fn set_sched_policy(pid: Pid, policy: SchedPolicy) -> anyhow::Result<()> {
+    if dry_run(format_args!("set scheduling policy of PID {pid} to {policy:?}")) {
+        dry_run_procs().policies.insert(pid, policy);
+        return Ok(());
+    }
+
+    Ok(hcbs_utils::prelude::set_sched_policy(pid, policy)?)
+}
+
+pub fn kill_pid(pid: Pid) -> anyhow::Result<()> {
+    if dry_run(format_args!("kill PID {pid}")) { return Ok(()); }
+
+    Ok(hcbs_utils::prelude::kill_pid(pid)?)
+}
+
+pub fn enable_hyperthreading() -> anyhow::Result<()> {
+    if dry_run(format_args!("enable hyperthreading")) { return Ok(()); }
+
+    Ok(hcbs_utils::prelude::enable_hyperthreading()?)
+}
+
+pub fn disable_hyperthreading() -> anyhow::Result<()> {
+    if dry_run(format_args!("disable hyperthreading")) { return Ok(()); }
+
+    Ok(hcbs_utils::prelude::disable_hyperthreading()?)
+}
+
+pub fn set_intel_pstate(pstate: intel::PState) -> anyhow::Result<()> {
+    if dry_run(format_args!("set intel-pstate state to {pstate:?}")) { return Ok(()); }
+
+    Ok(intel::set_pstate(pstate)?)
+}
+
+pub fn set_cpu_frequency_governor(cpu: CpuID, data: CpuFrequencyGovernorData) -> anyhow::Result<()> {
+    if dry_run(format_args!("set frequency governor of CPU {cpu} to {data:?}")) { return Ok(()); }
+
+    Ok(hcbs_utils::prelude::set_cpu_frequency_governor(cpu, data)?)
+}
+
+pub fn set_cpu_idle_state(cpu: CpuID, data: CpuIdleStates) -> anyhow::Result<()> {
+    if dry_run(format_args!("set idle states of CPU {cpu} to {data:?}")) { return Ok(()); }
+
+    Ok(hcbs_utils::prelude::set_cpu_idle_state(cpu, data)?)
+}
+
+pub fn write_file<P: AsRef<std::path::Path>>(path: P, data: &str) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    if dry_run(format_args!("write \"{data}\" to {}", path.display())) { return Ok(()); }
+
+    Ok(std::fs::write(path, data)?)
+}
+
+fn dry_run_procs() -> MutexGuard<'static, DryRunProcs> {
+    DRY_RUN_PROCS.lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
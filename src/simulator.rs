@@ -0,0 +1,313 @@
+use crate::manager::{AnalysisTask, Reservation};
+
+/// Managed cgroup to simulate, with the periodic tasks running inside it.
+#[derive(Debug, Clone)]
+pub struct SimCgroup {
+    pub name: String,
+    pub reservation: Reservation,
+    pub tasks: Vec<AnalysisTask>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SimulationOptions {
+    pub cpus: usize,
+    pub duration_us: u64,
+    /// Record the execution trace.
+    pub trace: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SimulationReport {
+    pub duration_us: u64,
+    pub cpus: usize,
+    pub cgroups: Vec<CgroupReport>,
+    pub tasks: Vec<TaskReport>,
+    pub trace: Vec<TraceSegment>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CgroupReport {
+    pub name: String,
+    pub reservation: Reservation,
+    /// Total execution time of the cgroup's tasks, over all the CPUs.
+    pub consumed_us: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct TaskReport {
+    pub cgroup: usize,
+    pub task: usize,
+    pub jobs: u64,
+    /// Jobs completed after their deadline, or not completed by their
+    /// deadline within the simulation.
+    pub misses: u64,
+    /// Response times of the completed jobs.
+    pub response_times_us: Vec<u64>,
+}
+
+/// Interval in which a job runs on a CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceSegment {
+    pub start_us: u64,
+    pub end_us: u64,
+    pub cpu: usize,
+    pub cgroup: usize,
+    pub task: usize,
+    pub job: u64,
+}
+
+#[derive(Debug)]
+struct Job {
+    cgroup: usize,
+    task: usize,
+    index: u64,
+    priority: u8,
+    release_us: u64,
+    deadline_us: u64,
+    remaining_us: u64,
+}
+
+/// Per-CPU server of a cgroup, replenished at every period.
+#[derive(Debug, Clone, Copy)]
+struct Server {
+    budget_us: u64,
+    deadline_us: u64,
+}
+
+impl CgroupReport {
+    /// Average number of CPUs used by the cgroup.
+    pub fn utilisation(&self, duration_us: u64) -> f64 {
+        self.consumed_us as f64 / duration_us as f64
+    }
+}
+
+impl TaskReport {
+    /// Response time below which the given fraction of the completed jobs
+    /// fall, e.g. 0.99 for the 99th percentile.
+    pub fn percentile(&self, fraction: f64) -> Option<u64> {
+        let mut response_times = self.response_times_us.clone();
+        response_times.sort();
+
+        let index = ((response_times.len() as f64 * fraction).ceil() as usize).saturating_sub(1);
+        response_times.get(index).copied()
+    }
+
+    pub fn mean(&self) -> Option<f64> {
+        if self.response_times_us.is_empty() {
+            return None;
+        }
+
+        Some(self.response_times_us.iter().sum::<u64>() as f64 / self.response_times_us.len() as f64)
+    }
+}
+
+/// Discrete-event simulation of hierarchical scheduling, modelled after HCBS:
+/// each cgroup has a server on every CPU with the cgroup's runtime and period,
+/// replenished at every period boundary. On each CPU, the servers with budget
+/// left are scheduled by EDF, and each cgroup runs its highest priority jobs
+/// on the CPUs where its servers are selected. Tasks release their first job
+/// at time zero, and every job runs for the task's WCET.
+pub fn simulate(cgroups: &[SimCgroup], options: &SimulationOptions) -> anyhow::Result<SimulationReport> {
+    if options.cpus == 0 {
+        anyhow::bail!("At least one CPU must be simulated.");
+    }
+
+    for cgroup in cgroups.iter() {
+        let Reservation { runtime_us, period_us } = cgroup.reservation;
+
+        if period_us == 0 || runtime_us > period_us {
+            anyhow::bail!("Invalid reservation of cgroup \"{}\": {runtime_us}us every {period_us}us.", cgroup.name);
+        }
+    }
+
+    let mut servers: Vec<Vec<Server>> =
+        cgroups.iter()
+        .map(|cgroup| vec![Server {
+            budget_us: cgroup.reservation.runtime_us,
+            deadline_us: cgroup.reservation.period_us,
+        }; options.cpus])
+        .collect();
+
+    let mut tasks: Vec<TaskReport> =
+        cgroups.iter().enumerate()
+        .flat_map(|(cgroup_index, cgroup)| (0..cgroup.tasks.len()).map(move |task| TaskReport {
+            cgroup: cgroup_index,
+            task,
+            jobs: 0,
+            misses: 0,
+            response_times_us: Vec::new(),
+        }))
+        .collect();
+
+    let mut next_release = vec![0u64; tasks.len()];
+    let mut consumed_us = vec![0u64; cgroups.len()];
+    let mut jobs: Vec<Job> = Vec::new();
+    let mut trace: Vec<TraceSegment> = Vec::new();
+    let mut now = 0;
+
+    while now < options.duration_us {
+        // Job releases
+        for (index, report) in tasks.iter_mut().enumerate() {
+            if next_release[index] != now {
+                continue;
+            }
+
+            let task = &cgroups[report.cgroup].tasks[report.task];
+            jobs.push(Job {
+                cgroup: report.cgroup,
+                task: report.task,
+                index: report.jobs,
+                priority: task.priority,
+                release_us: now,
+                deadline_us: now + task.deadline_us,
+                remaining_us: task.wcet_us,
+            });
+
+            report.jobs += 1;
+            next_release[index] += task.period_us;
+        }
+
+        // Budget replenishments
+        for (cgroup, cgroup_servers) in cgroups.iter().zip(servers.iter_mut()) {
+            for server in cgroup_servers.iter_mut().filter(|server| server.deadline_us == now) {
+                server.budget_us = cgroup.reservation.runtime_us;
+                server.deadline_us += cgroup.reservation.period_us;
+            }
+        }
+
+        // Ready jobs of each cgroup, by decreasing priority
+        let mut ready: Vec<Vec<usize>> = vec![Vec::new(); cgroups.len()];
+        for (index, job) in jobs.iter().enumerate() {
+            ready[job.cgroup].push(index);
+        }
+        for cgroup_ready in ready.iter_mut() {
+            cgroup_ready.sort_by_key(|&index| {
+                let job = &jobs[index];
+                (std::cmp::Reverse(job.priority), job.release_us, job.task)
+            });
+        }
+
+        // EDF among the servers of each CPU
+        let mut assigned = vec![0usize; cgroups.len()];
+        let mut running: Vec<Option<(usize, usize)>> = vec![None; options.cpus];
+        for (cpu, running) in running.iter_mut().enumerate() {
+            let selected =
+                (0..cgroups.len())
+                .filter(|&cgroup| servers[cgroup][cpu].budget_us > 0 && assigned[cgroup] < ready[cgroup].len())
+                .min_by_key(|&cgroup| (servers[cgroup][cpu].deadline_us, cgroup));
+
+            if let Some(cgroup) = selected {
+                *running = Some((cgroup, ready[cgroup][assigned[cgroup]]));
+                assigned[cgroup] += 1;
+            }
+        }
+
+        // Next event
+        let mut next = options.duration_us;
+        next = next_release.iter().fold(next, |next, &release| u64::min(next, release));
+        next = servers.iter().flatten().fold(next, |next, server| u64::min(next, server.deadline_us));
+        for (cpu, &running) in running.iter().enumerate() {
+            if let Some((cgroup, job)) = running {
+                next = u64::min(next, now + jobs[job].remaining_us);
+                next = u64::min(next, now + servers[cgroup][cpu].budget_us);
+            }
+        }
+
+        let elapsed = next - now;
+        for (cpu, &running) in running.iter().enumerate() {
+            let Some((cgroup, index)) = running
+                else { continue; };
+
+            let job = &mut jobs[index];
+            job.remaining_us -= elapsed;
+            servers[cgroup][cpu].budget_us -= elapsed;
+            consumed_us[cgroup] += elapsed;
+
+            if options.trace {
+                trace_run(&mut trace, TraceSegment {
+                    start_us: now,
+                    end_us: next,
+                    cpu,
+                    cgroup,
+                    task: job.task,
+                    job: job.index,
+                });
+            }
+        }
+
+        now = next;
+
+        // Job completions
+        jobs.retain(|job| {
+            if job.remaining_us > 0 {
+                return true;
+            }
+
+            let report = tasks.iter_mut()
+                .find(|report| report.cgroup == job.cgroup && report.task == job.task)
+                .unwrap();
+
+            report.response_times_us.push(now - job.release_us);
+            if now > job.deadline_us {
+                report.misses += 1;
+            }
+
+            false
+        });
+    }
+
+    // Jobs left unfinished past their deadline
+    for job in jobs.iter().filter(|job| job.deadline_us < now) {
+        if let Some(report) = tasks.iter_mut().find(|report| report.cgroup == job.cgroup && report.task == job.task) {
+            report.misses += 1;
+        }
+    }
+
+    Ok(SimulationReport {
+        duration_us: options.duration_us,
+        cpus: options.cpus,
+        cgroups: cgroups.iter().zip(consumed_us)
+            .map(|(cgroup, consumed_us)| CgroupReport {
+                name: cgroup.name.clone(),
+                reservation: cgroup.reservation,
+                consumed_us,
+            })
+            .collect(),
+        tasks,
+        trace,
+    })
+}
+
+impl SimulationReport {
+    pub fn misses(&self) -> u64 {
+        self.tasks.iter().map(|task| task.misses).sum()
+    }
+
+    /// Writes the trace as CSV, one execution interval per line.
+    pub fn write_trace<W: std::io::Write>(&self, mut out: W) -> anyhow::Result<()> {
+        writeln!(out, "start_us,end_us,cpu,cgroup,task,job")?;
+
+        for segment in self.trace.iter() {
+            writeln!(out, "{},{},{},{},{},{}",
+                segment.start_us, segment.end_us, segment.cpu,
+                self.cgroups[segment.cgroup].name, segment.task, segment.job)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Appends the execution interval to the trace, merging it with the previous
+/// interval of the same job on the same CPU if contiguous.
+fn trace_run(trace: &mut Vec<TraceSegment>, segment: TraceSegment) {
+    let previous =
+        trace.iter_mut().rev()
+        .find(|previous| previous.cpu == segment.cpu);
+
+    match previous {
+        Some(previous) if previous.end_us == segment.start_us
+            && (previous.cgroup, previous.task, previous.job) == (segment.cgroup, segment.task, segment.job) =>
+            previous.end_us = segment.end_us,
+        _ => trace.push(segment),
+    }
+}
@@ -0,0 +1,210 @@
+use std::path::PathBuf;
+
+use hcbs_utils::prelude::*;
+
+use crate::ops::{mount_cgroup_fs, set_cgroup_runtime_us, write_file};
+
+/// Original values of the system settings changed by the manager. The
+/// snapshot is written to disk before any change is made, so that the system
+/// can be restored even if the manager does not exit cleanly.
+///
+/// The snapshot is stored as text, one setting per line:
+/// ```text
+/// boot_id <boot id>
+/// root_runtime_us <runtime>
+/// file <path>\t<value>
+/// ```
+#[derive(Debug, PartialEq)]
+pub struct SystemSnapshot {
+    boot_id: String,
+    root_runtime_us: Option<u64>,
+    files: Vec<(PathBuf, String)>,
+}
+
+impl SystemSnapshot {
+    pub const PATH: &'static str = "/var/lib/hcbs-manager/snapshot";
+    const BOOT_ID_FILE: &'static str = "/proc/sys/kernel/random/boot_id";
+
+    /// Reads the current values of the given files, skipping the missing ones.
+    /// Files are restored in the given order.
+    pub fn capture(files: Vec<PathBuf>, root_runtime_us: Option<u64>) -> anyhow::Result<Self> {
+        let files =
+            files.into_iter()
+            .filter_map(|path| {
+                match std::fs::read_to_string(&path) {
+                    Ok(value) => Some((path, value.trim().to_owned())),
+                    Err(err) => {
+                        debug!("Skipping {} from the system snapshot: {err}", path.display());
+                        None
+                    },
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            boot_id: Self::current_boot_id()?,
+            root_runtime_us,
+            files,
+        })
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        use std::io::Write as _;
+
+        if crate::ops::dry_run(format_args!("save system state to {}", Self::PATH)) { return Ok(()); }
+
+        let path = std::path::Path::new(Self::PATH);
+        let tmp_path = path.with_extension("tmp");
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let data = self.to_text();
+
+        // Write the snapshot atomically, and make sure it reaches the disk
+        // before any change is made to the system.
+        let mut file = std::fs::File::create(&tmp_path)?;
+        file.write_all(data.as_bytes())?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, path)?;
+
+        Ok(())
+    }
+
+    pub fn load() -> anyhow::Result<Option<Self>> {
+        let data = match std::fs::read_to_string(Self::PATH) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        Self::parse(&data).map(Some)
+    }
+
+    fn to_text(&self) -> String {
+        let mut data = format!("boot_id {}\n", self.boot_id);
+        if let Some(runtime_us) = self.root_runtime_us {
+            data += &format!("root_runtime_us {runtime_us}\n");
+        }
+        for (path, value) in self.files.iter() {
+            data += &format!("file {}\t{value}\n", path.display());
+        }
+
+        data
+    }
+
+    fn parse(data: &str) -> anyhow::Result<Self> {
+        let mut boot_id = None;
+        let mut root_runtime_us = None;
+        let mut files = Vec::new();
+
+        for line in data.lines().filter(|line| !line.is_empty()) {
+            let Some((key, value)) = line.split_once(' ')
+                else { anyhow::bail!("Invalid system snapshot line: \"{line}\""); };
+
+            match key {
+                "boot_id" => { boot_id = Some(value.to_owned()); },
+                "root_runtime_us" => { root_runtime_us = Some(value.parse()?); },
+                "file" => {
+                    let Some((path, value)) = value.split_once('\t')
+                        else { anyhow::bail!("Invalid system snapshot line: \"{line}\""); };
+
+                    files.push((PathBuf::from(path), value.to_owned()));
+                },
+                _ => anyhow::bail!("Invalid system snapshot line: \"{line}\""),
+            }
+        }
+
+        let Some(boot_id) = boot_id
+            else { anyhow::bail!("Invalid system snapshot: missing boot id"); };
+
+        Ok(Self { boot_id, root_runtime_us, files })
+    }
+
+    pub fn remove() -> anyhow::Result<()> {
+        if crate::ops::dry_run(format_args!("remove system state at {}", Self::PATH)) { return Ok(()); }
+
+        match std::fs::remove_file(Self::PATH) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Whether the snapshot was taken since the last boot. Older snapshots are
+    /// stale, as rebooting resets all the saved settings.
+    pub fn is_from_current_boot(&self) -> anyhow::Result<bool> {
+        Ok(self.boot_id == Self::current_boot_id()?)
+    }
+
+    /// Writes back all the saved values. Values which did not change are
+    /// skipped, as some cannot be written at all, e.g. `notsupported` for
+    /// the SMT control. Errors are logged and do not stop the restore of the
+    /// remaining settings.
+    pub fn restore(&self) -> anyhow::Result<()> {
+        let mut failed = false;
+
+        for (path, value) in self.files.iter() {
+            if std::fs::read_to_string(path).is_ok_and(|current| current.trim() == value.as_str()) {
+                continue;
+            }
+
+            if let Err(err) = write_file(path, value) {
+                error!("Couldn't restore {} to \"{value}\": {err}", path.display());
+                failed = true;
+            }
+        }
+
+        if let Some(runtime_us) = self.root_runtime_us {
+            if let Err(err) = mount_cgroup_fs().and_then(|_| set_cgroup_runtime_us(ROOT_CGROUP, runtime_us)) {
+                error!("Couldn't restore the root cgroup runtime to {runtime_us}us: {err}");
+                failed = true;
+            }
+        }
+
+        if failed {
+            anyhow::bail!("Some settings couldn't be restored");
+        }
+
+        Ok(())
+    }
+
+    fn current_boot_id() -> anyhow::Result<String> {
+        Ok(std::fs::read_to_string(Self::BOOT_ID_FILE)?.trim().to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_round_trip() {
+        let snapshot = SystemSnapshot {
+            boot_id: "0f4c7a2e-8d1b-4f5e-9a3c-6b2d1e0f9a87".to_owned(),
+            root_runtime_us: Some(950000),
+            files: vec![
+                (PathBuf::from("/sys/devices/system/cpu/smt/control"), "on".to_owned()),
+                (PathBuf::from("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor"), "powersave".to_owned()),
+            ],
+        };
+
+        assert_eq!(SystemSnapshot::parse(&snapshot.to_text()).unwrap(), snapshot);
+    }
+
+    #[test]
+    fn snapshot_without_root_runtime() {
+        let snapshot = SystemSnapshot::parse("boot_id abc\nfile /sys/devices/system/cpu/smt/control\tnotsupported\n").unwrap();
+
+        assert_eq!(snapshot.root_runtime_us, None);
+        assert_eq!(snapshot.files, vec![(PathBuf::from("/sys/devices/system/cpu/smt/control"), "notsupported".to_owned())]);
+    }
+
+    #[test]
+    fn invalid_snapshots_are_rejected() {
+        assert!(SystemSnapshot::parse("root_runtime_us 950000\n").is_err());
+        assert!(SystemSnapshot::parse("boot_id abc\nfile /sys/devices/system/cpu/smt/control\n").is_err());
+        assert!(SystemSnapshot::parse("boot_id abc\nunknown 1\n").is_err());
+    }
+}
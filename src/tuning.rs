@@ -0,0 +1,632 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use hcbs_utils::prelude::*;
+
+use crate::ops::{
+    disable_hyperthreading,
+    enable_hyperthreading,
+    set_cpu_frequency_governor,
+    set_cpu_idle_state,
+    set_intel_pstate,
+    write_file,
+};
+
+const SMT_CONTROL_FILE: &str = "/sys/devices/system/cpu/smt/control";
+
+/// Backend used to tune the system for real-time workloads. Backends differ
+/// in how they drive the CPU frequency, while hyperthreading and idle states
+/// are handled the same way by default. Per-CPU settings are only applied to
+/// the given CPUs, skipping the offline ones.
+pub trait TuningBackend: std::fmt::Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether the backend can be used on this machine.
+    fn is_available(&self) -> anyhow::Result<bool>;
+
+    fn get_frequency_state(&self, cpus: &[CpuID]) -> anyhow::Result<FrequencyState>;
+
+    /// Fixes the frequency of the given CPUs to their maximum.
+    fn set_max_frequency(&self, cpus: &[CpuID]) -> anyhow::Result<()>;
+
+    fn set_frequency_state(&self, state: FrequencyState) -> anyhow::Result<()>;
+
+    /// Sysfs files changed when fixing the frequency of the given CPUs, in
+    /// the order they have to be restored.
+    fn frequency_files(&self, cpus: &[CpuID]) -> Vec<PathBuf>;
+
+    fn hyperthreading_files(&self) -> Vec<PathBuf> {
+        vec![PathBuf::from(SMT_CONTROL_FILE)]
+    }
+
+    fn idle_state_files(&self, cpus: &[CpuID]) -> anyhow::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+
+        for cpu in online_cpus(cpus)? {
+            let dir = PathBuf::from(format!("/sys/devices/system/cpu/cpu{cpu}/cpuidle"));
+            if !dir.exists() { continue; }
+
+            let mut states: Vec<_> =
+                std::fs::read_dir(&dir)?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| {
+                    path.file_name()
+                        .and_then(|name| name.to_str())
+                        .is_some_and(|name| name.starts_with("state"))
+                })
+                .collect();
+            states.sort();
+
+            files.extend(states.into_iter().map(|state| state.join("disable")));
+        }
+
+        Ok(files)
+    }
+
+    fn get_hyperthreading(&self) -> anyhow::Result<bool> {
+        Ok(hyperthreading_enabled()?)
+    }
+
+    fn set_hyperthreading(&self, enabled: bool) -> anyhow::Result<()> {
+        if enabled { enable_hyperthreading()? }
+        else { disable_hyperthreading()? }
+
+        Ok(())
+    }
+
+    fn get_idle_states(&self, cpus: &[CpuID]) -> anyhow::Result<Vec<(CpuID, CpuIdleStates)>> {
+        online_cpus(cpus)?.into_iter()
+            .map(|cpu| Ok((cpu, get_cpu_idle_state(cpu)?)) )
+            .collect()
+    }
+
+    /// Disables the idle states of the given CPUs.
+    fn disable_idle_states(&self, cpus: &[CpuID]) -> anyhow::Result<()> {
+        for cpu in online_cpus(cpus)? {
+            let cpu = cpu as u32;
+            set_cpu_idle_state(cpu, CpuIdleStates::disabled_for_cpu(cpu)?)?;
+        }
+
+        Ok(())
+    }
+
+    fn set_idle_states(&self, states: Vec<(CpuID, CpuIdleStates)>) -> anyhow::Result<()> {
+        for (cpu, data) in states.into_iter() {
+            set_cpu_idle_state(cpu, data)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TuningBackendKind {
+    /// Pick the first available backend.
+    Auto,
+    IntelPstate,
+    AmdPstate,
+    /// Generic cpufreq governors, e.g. acpi-cpufreq or ARM cpufreq drivers.
+    Cpufreq,
+    /// Do not tune the system.
+    None,
+}
+
+/// Predefined sets of tuning steps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TuningProfile {
+    /// Do not tune the system.
+    None,
+    /// Only fix the CPU frequencies.
+    Frequency,
+    /// Disable hyperthreading and idle states, and fix the CPU frequencies.
+    Full,
+}
+
+/// Tuning steps to apply, and CPUs to apply them to.
+#[derive(Debug, Clone)]
+pub struct TuningOptions {
+    pub hyperthreading: bool,
+    pub frequency: bool,
+    pub idle_states: bool,
+    /// CPUs running the managed reservations. If not given, the per-CPU
+    /// steps follow the CPUs of the managed cgroups through a [`CpuTuner`].
+    /// Hyperthreading and the frequency driver are system-wide anyway.
+    pub cpus: Option<Vec<CpuID>>,
+}
+
+/// Applies the per-CPU tuning steps to the CPUs running the managed
+/// reservations, as these change. CPUs are tuned once they run a reservation,
+/// and restored once they no longer do, or when the tuner is dropped. The
+/// system-wide steps are left to [`setup_realtime_system`].
+pub struct CpuTuner {
+    backend: Arc<dyn TuningBackend>,
+    options: TuningOptions,
+    cpus: Vec<CpuID>,
+    governors: Vec<(CpuID, CpuFrequencyGovernorData)>,
+    idle_states: Vec<(CpuID, CpuIdleStates)>,
+}
+
+/// Frequency state saved by a backend, to be restored on exit.
+pub struct FrequencyState {
+    driver: DriverState,
+    governors: Vec<(CpuID, CpuFrequencyGovernorData)>,
+}
+
+enum DriverState {
+    None,
+    IntelPState(intel::PState),
+    AmdPState(String),
+}
+
+/// State of the steps which were applied, `None` for the skipped ones.
+pub struct RealtimeResetData {
+    hyperthreading_enabled: Option<bool>,
+    frequency: Option<FrequencyState>,
+    cpu_idle_states: Option<Vec<(CpuID, CpuIdleStates)>>,
+}
+
+pub fn tuning_backend(kind: TuningBackendKind) -> anyhow::Result<Arc<dyn TuningBackend>> {
+    let backends: Vec<Box<dyn TuningBackend>> = match kind {
+        TuningBackendKind::Auto => vec![
+            Box::new(IntelPStateBackend),
+            Box::new(AmdPStateBackend),
+            Box::new(CpuFreqBackend),
+            Box::new(NoopBackend),
+        ],
+        TuningBackendKind::IntelPstate => vec![Box::new(IntelPStateBackend)],
+        TuningBackendKind::AmdPstate => vec![Box::new(AmdPStateBackend)],
+        TuningBackendKind::Cpufreq => vec![Box::new(CpuFreqBackend)],
+        TuningBackendKind::None => vec![Box::new(NoopBackend)],
+    };
+
+    for backend in backends.into_iter() {
+        if backend.is_available()? {
+            info!("Using the \"{}\" system tuning backend", backend.name());
+            return Ok(backend.into());
+        }
+
+        if kind != TuningBackendKind::Auto {
+            anyhow::bail!("System tuning backend \"{}\" is not available", backend.name());
+        }
+    }
+
+    unreachable!("the no-op backend is always available")
+}
+
+/// Sysfs files changed by the given tuning steps, in the order they have to be
+/// restored. Without explicit CPUs, these include the files of every CPU which
+/// may run a reservation.
+pub fn tuning_state_files(backend: &dyn TuningBackend, options: &TuningOptions) -> anyhow::Result<Vec<PathBuf>> {
+    let cpus = tunable_cpus(options)?;
+    let mut files = Vec::new();
+
+    if options.hyperthreading {
+        files.extend(backend.hyperthreading_files());
+    }
+    if options.frequency {
+        files.extend(backend.frequency_files(&cpus));
+    }
+    if options.idle_states {
+        files.extend(backend.idle_state_files(&cpus)?);
+    }
+
+    Ok(files)
+}
+
+/// Applies the system-wide tuning steps, and the per-CPU ones to the explicitly
+/// given CPUs. Otherwise the per-CPU steps are left to a [`CpuTuner`].
+pub fn setup_realtime_system(backend: &dyn TuningBackend, options: &TuningOptions) -> anyhow::Result<RealtimeResetData> {
+    let cpus = options.cpus.clone().unwrap_or_default();
+
+    // Get current system state
+    let hyperthreading_enabled =
+        if options.hyperthreading { Some(backend.get_hyperthreading()?) }
+        else { None };
+    let frequency =
+        if options.frequency { Some(backend.get_frequency_state(&cpus)?) }
+        else { None };
+    let cpu_idle_states =
+        if options.idle_states { Some(backend.get_idle_states(&cpus)?) }
+        else { None };
+
+    // Set Real-Time system state
+    if hyperthreading_enabled == Some(true) {
+        backend.set_hyperthreading(false)?;
+    }
+        // offline CPUs are skipped, as disabling hyperthreading switches off
+        // some logical cores.
+    if frequency.is_some() {
+        backend.set_max_frequency(&cpus)?;
+    }
+    if cpu_idle_states.is_some() {
+        backend.disable_idle_states(&cpus)?;
+    }
+
+    Ok(RealtimeResetData {
+        hyperthreading_enabled,
+        frequency,
+        cpu_idle_states
+    })
+}
+
+pub fn reset_realtime_system(backend: &dyn TuningBackend, data: RealtimeResetData) -> anyhow::Result<()> {
+    // Reset Real-Time system state
+    if data.hyperthreading_enabled == Some(true) { backend.set_hyperthreading(true)? }
+    if let Some(frequency) = data.frequency {
+        backend.set_frequency_state(frequency)?;
+    }
+    if let Some(cpu_idle_states) = data.cpu_idle_states {
+        backend.set_idle_states(cpu_idle_states)?;
+    }
+
+    Ok(())
+}
+
+/// Parses a CPU list in the kernel format, e.g. `0-3,6`.
+pub fn parse_cpu_list(data: &str) -> anyhow::Result<Vec<CpuID>> {
+    let mut cpus = Vec::new();
+
+    for range in data.trim().split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((first, last)) => {
+                let first: CpuID = first.trim().parse()?;
+                let last: CpuID = last.trim().parse()?;
+
+                cpus.extend(first ..= last);
+            },
+            None => cpus.push(range.trim().parse()?),
+        }
+    }
+
+    Ok(cpus)
+}
+
+impl TuningProfile {
+    pub fn options(&self, cpus: Option<Vec<CpuID>>) -> TuningOptions {
+        let (hyperthreading, frequency, idle_states) = match self {
+            TuningProfile::None => (false, false, false),
+            TuningProfile::Frequency => (false, true, false),
+            TuningProfile::Full => (true, true, true),
+        };
+
+        TuningOptions { hyperthreading, frequency, idle_states, cpus }
+    }
+}
+
+#[derive(Debug)]
+struct IntelPStateBackend;
+
+#[derive(Debug)]
+struct AmdPStateBackend;
+
+#[derive(Debug)]
+struct CpuFreqBackend;
+
+#[derive(Debug)]
+struct NoopBackend;
+
+impl IntelPStateBackend {
+    const INTEL_PSTATE_DIR: &'static str = "/sys/devices/system/cpu/intel_pstate";
+}
+
+impl TuningBackend for IntelPStateBackend {
+    fn name(&self) -> &'static str { "intel-pstate" }
+
+    fn is_available(&self) -> anyhow::Result<bool> {
+        Ok(intel::has_intel_pstate()?)
+    }
+
+    fn get_frequency_state(&self, cpus: &[CpuID]) -> anyhow::Result<FrequencyState> {
+        Ok(FrequencyState {
+            driver: DriverState::IntelPState(intel::get_pstate()?),
+            governors: get_frequency_governors(cpus)?,
+        })
+    }
+
+    fn set_max_frequency(&self, cpus: &[CpuID]) -> anyhow::Result<()> {
+        set_intel_pstate(intel::PState::fix_performance())?;
+
+        set_max_frequency_governors(cpus)
+    }
+
+    fn set_frequency_state(&self, state: FrequencyState) -> anyhow::Result<()> {
+        if let DriverState::IntelPState(pstate) = state.driver {
+            set_intel_pstate(pstate)?;
+        }
+
+        set_frequency_governors(state.governors)
+    }
+
+    fn frequency_files(&self, cpus: &[CpuID]) -> Vec<PathBuf> {
+        let mut files: Vec<_> =
+            ["status", "no_turbo", "min_perf_pct", "max_perf_pct"].into_iter()
+            .map(|file| PathBuf::from(Self::INTEL_PSTATE_DIR).join(file))
+            .collect();
+
+        files.extend(cpufreq_files(cpus));
+        files
+    }
+}
+
+impl AmdPStateBackend {
+    const STATUS_FILE: &'static str = "/sys/devices/system/cpu/amd_pstate/status";
+
+    /// In passive mode amd-pstate accepts the generic cpufreq governors.
+    const PASSIVE_STATUS: &'static str = "passive";
+}
+
+impl TuningBackend for AmdPStateBackend {
+    fn name(&self) -> &'static str { "amd-pstate" }
+
+    fn is_available(&self) -> anyhow::Result<bool> {
+        Ok(std::path::Path::new(Self::STATUS_FILE).exists())
+    }
+
+    fn get_frequency_state(&self, cpus: &[CpuID]) -> anyhow::Result<FrequencyState> {
+        let status = std::fs::read_to_string(Self::STATUS_FILE)?.trim().to_owned();
+
+        Ok(FrequencyState {
+            driver: DriverState::AmdPState(status),
+            governors: get_frequency_governors(cpus)?,
+        })
+    }
+
+    fn set_max_frequency(&self, cpus: &[CpuID]) -> anyhow::Result<()> {
+        write_file(Self::STATUS_FILE, Self::PASSIVE_STATUS)?;
+
+        set_max_frequency_governors(cpus)
+    }
+
+    fn set_frequency_state(&self, state: FrequencyState) -> anyhow::Result<()> {
+        if let DriverState::AmdPState(status) = state.driver {
+            write_file(Self::STATUS_FILE, &status)?;
+        }
+
+        set_frequency_governors(state.governors)
+    }
+
+    fn frequency_files(&self, cpus: &[CpuID]) -> Vec<PathBuf> {
+        let mut files = vec![PathBuf::from(Self::STATUS_FILE)];
+
+        files.extend(cpufreq_files(cpus));
+        files
+    }
+}
+
+impl CpuFreqBackend {
+    const CPUFREQ_DIR: &'static str = "/sys/devices/system/cpu/cpu0/cpufreq";
+}
+
+impl TuningBackend for CpuFreqBackend {
+    fn name(&self) -> &'static str { "cpufreq" }
+
+    fn is_available(&self) -> anyhow::Result<bool> {
+        Ok(std::path::Path::new(Self::CPUFREQ_DIR).exists())
+    }
+
+    fn get_frequency_state(&self, cpus: &[CpuID]) -> anyhow::Result<FrequencyState> {
+        Ok(FrequencyState {
+            driver: DriverState::None,
+            governors: get_frequency_governors(cpus)?,
+        })
+    }
+
+    fn set_max_frequency(&self, cpus: &[CpuID]) -> anyhow::Result<()> {
+        set_max_frequency_governors(cpus)
+    }
+
+    fn set_frequency_state(&self, state: FrequencyState) -> anyhow::Result<()> {
+        set_frequency_governors(state.governors)
+    }
+
+    fn frequency_files(&self, cpus: &[CpuID]) -> Vec<PathBuf> {
+        cpufreq_files(cpus)
+    }
+}
+
+impl TuningBackend for NoopBackend {
+    fn name(&self) -> &'static str { "none" }
+
+    fn is_available(&self) -> anyhow::Result<bool> { Ok(true) }
+
+    fn get_frequency_state(&self, _cpus: &[CpuID]) -> anyhow::Result<FrequencyState> {
+        Ok(FrequencyState {
+            driver: DriverState::None,
+            governors: Vec::with_capacity(0),
+        })
+    }
+
+    fn set_max_frequency(&self, _cpus: &[CpuID]) -> anyhow::Result<()> { Ok(()) }
+
+    fn set_frequency_state(&self, _state: FrequencyState) -> anyhow::Result<()> { Ok(()) }
+
+    fn frequency_files(&self, _cpus: &[CpuID]) -> Vec<PathBuf> { Vec::with_capacity(0) }
+
+    fn hyperthreading_files(&self) -> Vec<PathBuf> { Vec::with_capacity(0) }
+
+    fn idle_state_files(&self, _cpus: &[CpuID]) -> anyhow::Result<Vec<PathBuf>> { Ok(Vec::with_capacity(0)) }
+
+    fn get_hyperthreading(&self) -> anyhow::Result<bool> { Ok(false) }
+
+    fn set_hyperthreading(&self, _enabled: bool) -> anyhow::Result<()> { Ok(()) }
+
+    fn get_idle_states(&self, _cpus: &[CpuID]) -> anyhow::Result<Vec<(CpuID, CpuIdleStates)>> { Ok(Vec::with_capacity(0)) }
+
+    fn disable_idle_states(&self, _cpus: &[CpuID]) -> anyhow::Result<()> { Ok(()) }
+
+    fn set_idle_states(&self, _states: Vec<(CpuID, CpuIdleStates)>) -> anyhow::Result<()> { Ok(()) }
+}
+
+impl CpuTuner {
+    pub fn new(backend: Arc<dyn TuningBackend>, options: TuningOptions) -> Self {
+        Self {
+            backend,
+            options,
+            cpus: Vec::with_capacity(0),
+            governors: Vec::with_capacity(0),
+            idle_states: Vec::with_capacity(0),
+        }
+    }
+
+    /// Tunes the given CPUs, and restores the tuned CPUs which are not given.
+    pub fn tune(&mut self, cpus: &[CpuID]) -> anyhow::Result<()> {
+        let released: Vec<_> =
+            self.cpus.iter()
+            .copied()
+            .filter(|cpu| !cpus.contains(cpu))
+            .collect();
+
+        if !released.is_empty() {
+            self.cpus.retain(|cpu| !released.contains(cpu));
+            self.restore(&released)?;
+            info!("Restored CPUs {released:?}, which no longer run reservations");
+        }
+
+        let added: Vec<_> =
+            online_cpus(cpus)?.into_iter()
+            .filter(|cpu| !self.cpus.contains(cpu))
+            .collect();
+
+        if added.is_empty() {
+            return Ok(());
+        }
+
+        // The state is saved before any change, so that partially tuned CPUs
+        // are restored as well.
+        self.cpus.extend(added.iter().copied());
+        if self.options.frequency {
+            self.governors.extend(self.backend.get_frequency_state(&added)?.governors);
+        }
+        if self.options.idle_states {
+            self.idle_states.extend(self.backend.get_idle_states(&added)?);
+        }
+
+        if self.options.frequency {
+            self.backend.set_max_frequency(&added)?;
+        }
+        if self.options.idle_states {
+            self.backend.disable_idle_states(&added)?;
+        }
+
+        info!("Tuned CPUs {added:?}, which run reservations");
+
+        Ok(())
+    }
+
+    /// Restores the per-CPU state of the given CPUs, leaving the frequency
+    /// driver to [`reset_realtime_system`].
+    fn restore(&mut self, cpus: &[CpuID]) -> anyhow::Result<()> {
+        let governors = take_cpus(&mut self.governors, cpus);
+        let idle_states = take_cpus(&mut self.idle_states, cpus);
+
+        let frequency = self.backend.set_frequency_state(FrequencyState { driver: DriverState::None, governors });
+        let idle_states = self.backend.set_idle_states(idle_states);
+
+        frequency.and(idle_states)
+    }
+}
+
+impl std::fmt::Debug for CpuTuner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CpuTuner")
+            .field("backend", &self.backend.name())
+            .field("options", &self.options)
+            .field("cpus", &self.cpus)
+            .finish()
+    }
+}
+
+impl Drop for CpuTuner {
+    fn drop(&mut self) {
+        let cpus = std::mem::take(&mut self.cpus);
+
+        if let Err(err) = self.restore(&cpus) {
+            error!("Couldn't restore the tuned CPUs {cpus:?}: {err}");
+        }
+    }
+}
+
+/// CPUs which may be tuned: the given ones, or all of them.
+fn tunable_cpus(options: &TuningOptions) -> anyhow::Result<Vec<CpuID>> {
+    Ok(match &options.cpus {
+        Some(cpus) => cpus.clone(),
+        None => CpuSet::all()?.iter().copied().collect(),
+    })
+}
+
+/// Removes the state of the given CPUs from the saved ones, and returns it.
+fn take_cpus<T>(states: &mut Vec<(CpuID, T)>, cpus: &[CpuID]) -> Vec<(CpuID, T)> {
+    let (taken, kept) = std::mem::take(states).into_iter().partition(|(cpu, _)| cpus.contains(cpu));
+    *states = kept;
+
+    taken
+}
+
+/// Filters out the offline CPUs from the given ones.
+fn online_cpus(cpus: &[CpuID]) -> anyhow::Result<Vec<CpuID>> {
+    Ok(CpuSet::all()?.iter()
+        .copied()
+        .filter(|cpu| cpus.contains(cpu))
+        .collect())
+}
+
+fn get_frequency_governors(cpus: &[CpuID]) -> anyhow::Result<Vec<(CpuID, CpuFrequencyGovernorData)>> {
+    online_cpus(cpus)?.into_iter()
+        .map(|cpu| Ok((cpu, get_cpu_frequency_governor(cpu)?)) )
+        .collect()
+}
+
+fn set_max_frequency_governors(cpus: &[CpuID]) -> anyhow::Result<()> {
+    for cpu in online_cpus(cpus)? {
+        let freqs = get_cpu_frequency(cpu)?;
+
+        set_cpu_frequency_governor(cpu, CpuFrequencyGovernorData::fixed_frequency(freqs.max_frequency_mhz))?;
+    }
+
+    Ok(())
+}
+
+fn set_frequency_governors(governors: Vec<(CpuID, CpuFrequencyGovernorData)>) -> anyhow::Result<()> {
+    for (cpu, data) in governors.into_iter() {
+        set_cpu_frequency_governor(cpu, data)?;
+    }
+
+    Ok(())
+}
+
+/// Per-CPU cpufreq files changed by the frequency governors. The governor is
+/// restored first, then the minimum frequency, which is never above the fixed
+/// maximum, and finally the maximum frequency.
+fn cpufreq_files(cpus: &[CpuID]) -> Vec<PathBuf> {
+    cpus.iter()
+        .flat_map(|cpu| {
+            ["scaling_governor", "scaling_min_freq", "scaling_max_freq"].into_iter()
+                .map(move |file| PathBuf::from(format!("/sys/devices/system/cpu/cpu{cpu}/cpufreq/{file}")))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_lists_use_the_kernel_format() {
+        assert_eq!(parse_cpu_list("0-3,6\n").unwrap(), vec![0, 1, 2, 3, 6]);
+        assert_eq!(parse_cpu_list("5").unwrap(), vec![5]);
+        assert!(parse_cpu_list("").unwrap().is_empty());
+        assert!(parse_cpu_list("0-a").is_err());
+    }
+
+    #[test]
+    fn profiles_select_the_tuning_steps() {
+        let steps = |options: TuningOptions| (options.hyperthreading, options.frequency, options.idle_states);
+
+        assert_eq!(steps(TuningProfile::None.options(None)), (false, false, false));
+        assert_eq!(steps(TuningProfile::Frequency.options(None)), (false, true, false));
+        assert_eq!(steps(TuningProfile::Full.options(None)), (true, true, true));
+        assert_eq!(TuningProfile::Full.options(Some(vec![2, 3])).cpus, Some(vec![2, 3]));
+    }
+}